@@ -0,0 +1,493 @@
+//! Typed async client for a deployed `CrossContractFlipper`.
+//!
+//! Built on the `ink_e2e` node client, so calls are encoded with the
+//! contract's own generated call builders instead of hand-rolled extrinsics.
+//! Every transaction is dry-run first: errors returned by the contract are
+//! surfaced as [`Error::Contract`] without submitting anything.
+//!
+//! Like `ink_e2e`, the client panics on node failures it can't attribute to
+//! the call itself, such as a dropped connection while waiting for inclusion.
+
+use crate::{
+    cross_contract_flipper::{
+        AccountFreeze, AuctionSettled, AuditKind, AuditLog, BidPlaced, CallFailed,
+        CodeHashApproval, ConfirmationExpired, CrossContractFlipper, CrossContractFlipperRef,
+        DelegateCalled, DelegateUpdated, DelegationDisabled, DependencyUnlocked,
+        Error as ContractError, FlipConfirmed, FlipOutcome, FlipScheduled, FlipVetoed, Flipped,
+        FlippedBatch, GovernanceAction, KeeperPaid, KeyRotated, ProposalCreated, ProposalExecuted,
+        Received, RelayerPaid, ScheduledFlipFailed, SessionKeyRegistered, SessionKeyRevoked,
+        Subscribed, ValuesSet, Voted,
+    },
+    FlipperEnvironment,
+};
+use ink::env::Environment;
+use ink::primitives::{AccountId, Hash};
+use ink::scale::Decode;
+use ink_e2e::{
+    subxt::{backend::rpc::RpcClient, utils::AccountId32, OnlineClient},
+    ContractsBackend, Keypair, PolkadotConfig,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Errors the client can return.
+#[derive(Debug)]
+pub enum Error {
+    /// Connecting to the node failed.
+    Connection(ink_e2e::subxt::Error),
+    /// Dry-running or submitting the call failed.
+    Call(ink_e2e::Error),
+    /// The message couldn't be dispatched, e.g. the contract at the address
+    /// isn't a `CrossContractFlipper`.
+    Dispatch(ink::LangError),
+    /// The contract returned an error.
+    Contract(ContractError),
+    /// Reading blocks or events from the node failed.
+    Events(ink_e2e::subxt::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connection(err) => write!(f, "connecting to the node failed: {err}"),
+            Self::Call(err) => write!(f, "calling the contract failed: {err}"),
+            Self::Dispatch(ink::LangError::CouldNotReadInput) => write!(
+                f,
+                "the contract couldn't decode the input (unknown selector or \
+                 mismatched arguments), is it a `CrossContractFlipper` of the \
+                 same version?"
+            ),
+            Self::Dispatch(err) => write!(f, "dispatching the message failed: {err:?}"),
+            Self::Contract(err) => write!(
+                f,
+                "the contract returned an error: {err:?} (code {})",
+                err.code()
+            ),
+            Self::Events(err) => write!(f, "reading events failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ink_e2e::Error> for Error {
+    fn from(err: ink_e2e::Error) -> Self {
+        Self::Call(err)
+    }
+}
+
+/// An event emitted by the contract, as found in a finalized block.
+#[derive(Debug, Clone)]
+pub struct ContractEvent {
+    pub block: u64,
+    pub topics: Vec<[u8; 32]>,
+    /// SCALE encoded fields of the event.
+    pub data: Vec<u8>,
+}
+
+impl ContractEvent {
+    /// Decodes the event, if its signature topic belongs to one of the
+    /// contract's events.
+    pub fn decode(&self) -> Option<DecodedEvent> {
+        let signature = *self.topics.first()?;
+        macro_rules! decode {
+            ($($event:ident { $($field:ident),* }),* $(,)?) => {
+                $(
+                    if Some(signature) == <$event as ink::env::Event>::SIGNATURE_TOPIC {
+                        let event = <$event as Decode>::decode(&mut self.data.as_slice()).ok()?;
+                        return Some(DecodedEvent {
+                            name: stringify!($event),
+                            fields: vec![$((stringify!($field), event.$field.display())),*],
+                        });
+                    }
+                )*
+            };
+        }
+        decode! {
+            Flipped { by, value, memo },
+            FlippedBatch { by, count, first_value, last_value },
+            FlipConfirmed { id, by, confirmations },
+            ConfirmationExpired { id, confirmations },
+            FlipVetoed { guardian, value },
+            SessionKeyRegistered { account, key, expires_at },
+            SessionKeyRevoked { account },
+            FlipScheduled { id, by, at },
+            KeeperPaid { keeper, executed, bounty },
+            ScheduledFlipFailed { id, by, code },
+            Subscribed { account, expires_at },
+            BidPlaced { auction, bidder, total },
+            AuctionSettled { auction, winner, bid },
+            DelegateCalled { target, gas_used },
+            DelegateUpdated { old, new },
+            DelegationDisabled { last },
+            DependencyUnlocked { code_hash },
+            KeyRotated { old, new, dropped_flips },
+            AuditLog { seq, kind, data },
+            CodeHashApproval { code_hash, approved },
+            AccountFreeze { account, frozen },
+            ProposalCreated { id, proposer, actions },
+            Voted { id, voter, support, weight },
+            ProposalExecuted { id },
+            RelayerPaid { relayer, signer, fee },
+            Received { from, amount },
+            CallFailed { index, code },
+            ValuesSet { count, set_true },
+        }
+        None
+    }
+}
+
+/// Serializes as `{ "block", "topics", "data" }`, with bytes as hex.
+impl Serialize for ContractEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let topics: Vec<_> = self.topics.iter().map(|topic| to_hex(topic)).collect();
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("block", &self.block)?;
+        map.serialize_entry("topics", &topics)?;
+        map.serialize_entry("data", &to_hex(&self.data))?;
+        map.end()
+    }
+}
+
+/// An event of the contract, decoded into its name and fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedEvent {
+    pub name: &'static str,
+    /// Field names and their values, formatted for display.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Serializes as `{ "name", "fields": { field: value, ... } }`.
+impl Serialize for DecodedEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Fields<'a>(&'a [(&'static str, String)]);
+
+        impl Serialize for Fields<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(self.0.len()))?;
+                for (field, value) in self.0 {
+                    map.serialize_entry(field, value)?;
+                }
+                map.end()
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("name", self.name)?;
+        map.serialize_entry("fields", &Fields(&self.fields))?;
+        map.end()
+    }
+}
+
+/// Formats as `Name field=value ...`.
+impl fmt::Display for DecodedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        for (field, value) in &self.fields {
+            write!(f, " {field}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How event fields are formatted: accounts as SS58 addresses and hashes
+/// as hex.
+trait DisplayField {
+    fn display(&self) -> String;
+}
+
+impl DisplayField for AccountId {
+    fn display(&self) -> String {
+        AccountId32::from(*AsRef::<[u8; 32]>::as_ref(self)).to_string()
+    }
+}
+
+impl DisplayField for Hash {
+    fn display(&self) -> String {
+        to_hex(self.as_ref())
+    }
+}
+
+impl DisplayField for Option<Hash> {
+    fn display(&self) -> String {
+        match self {
+            Some(hash) => hash.display(),
+            None => String::from("none"),
+        }
+    }
+}
+
+impl DisplayField for Vec<GovernanceAction> {
+    fn display(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl DisplayField for AuditKind {
+    fn display(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl DisplayField for Vec<u8> {
+    fn display(&self) -> String {
+        to_hex(self)
+    }
+}
+
+impl DisplayField for Option<Vec<u8>> {
+    fn display(&self) -> String {
+        match self {
+            Some(bytes) => to_hex(bytes),
+            None => String::from("none"),
+        }
+    }
+}
+
+macro_rules! display_field_via_to_string {
+    ($($ty:ty),*) => {
+        $(
+            impl DisplayField for $ty {
+                fn display(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+display_field_via_to_string!(bool, u16, u32, u64, u128);
+
+/// Formats `bytes` as `0x` prefixed hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("0x{digits}")
+}
+
+/// Balance type of the contract's environment.
+pub type Balance = <FlipperEnvironment as Environment>::Balance;
+
+/// What a transaction needs, as measured by dry-running it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Estimate {
+    pub ref_time: u64,
+    pub proof_size: u64,
+    /// Storage deposit the transaction charges, zero if it refunds one.
+    pub storage_deposit: Balance,
+}
+
+impl Estimate {
+    fn of<V>(dry_run: &ink_e2e::CallDryRunResult<FlipperEnvironment, V>) -> Self {
+        let result = &dry_run.exec_result;
+        Self {
+            ref_time: result.gas_required.ref_time(),
+            proof_size: result.gas_required.proof_size(),
+            storage_deposit: result.storage_deposit.charge_or_zero(),
+        }
+    }
+}
+
+/// Connection to a node, bound to one deployed `CrossContractFlipper`.
+pub struct FlipperClient {
+    client: ink_e2e::Client<PolkadotConfig, FlipperEnvironment>,
+    api: OnlineClient<PolkadotConfig>,
+    contract: AccountId,
+}
+
+impl FlipperClient {
+    /// Connects to the node at `url`, e.g. `ws://127.0.0.1:9944`, to talk
+    /// to the contract at `contract`.
+    pub async fn connect(url: &str, contract: AccountId) -> Result<Self, Error> {
+        let (client, api) = Self::connect_node(url, Vec::<PathBuf>::new()).await?;
+        Ok(Self {
+            client,
+            api,
+            contract,
+        })
+    }
+
+    /// Instantiates a new contract from the Wasm blob at `wasm`, signed by
+    /// `signer`, and connects to it.
+    ///
+    /// The arguments are the ones of the `new` constructor.
+    pub async fn deploy(
+        url: &str,
+        signer: &Keypair,
+        wasm: &Path,
+        init_value: bool,
+        code_hash: Hash,
+    ) -> Result<Self, Error> {
+        let (mut client, api) = Self::connect_node(url, [wasm]).await?;
+        let mut constructor = CrossContractFlipperRef::new(init_value, code_hash);
+        let contract = client
+            .instantiate(&registry_name(wasm), signer, &mut constructor)
+            .submit()
+            .await?
+            .account_id;
+        Ok(Self {
+            client,
+            api,
+            contract,
+        })
+    }
+
+    /// Estimates what `deploy` would cost, without submitting anything.
+    pub async fn estimate_deploy(
+        url: &str,
+        signer: &Keypair,
+        wasm: &Path,
+        init_value: bool,
+        code_hash: Hash,
+    ) -> Result<Estimate, Error> {
+        let (mut client, _) = Self::connect_node(url, [wasm]).await?;
+        let mut constructor = CrossContractFlipperRef::new(init_value, code_hash);
+        let result = client
+            .instantiate(&registry_name(wasm), signer, &mut constructor)
+            .dry_run()
+            .await?
+            .contract_result;
+        Ok(Estimate {
+            ref_time: result.gas_required.ref_time(),
+            proof_size: result.gas_required.proof_size(),
+            storage_deposit: result.storage_deposit.charge_or_zero(),
+        })
+    }
+
+    async fn connect_node<P: Into<PathBuf>>(
+        url: &str,
+        contracts: impl IntoIterator<Item = P>,
+    ) -> Result<
+        (
+            ink_e2e::Client<PolkadotConfig, FlipperEnvironment>,
+            OnlineClient<PolkadotConfig>,
+        ),
+        Error,
+    > {
+        let rpc = RpcClient::from_url(url).await.map_err(Error::Connection)?;
+        let api = OnlineClient::from_rpc_client(rpc.clone())
+            .await
+            .map_err(Error::Connection)?;
+        let client = ink_e2e::Client::new(rpc, contracts)
+            .await
+            .map_err(Error::Connection)?;
+        Ok((client, api))
+    }
+
+    /// Returns the account id of the contract this client talks to.
+    pub fn contract(&self) -> AccountId {
+        self.contract
+    }
+
+    /// Flips the value through the delegate, signed by `signer`.
+    ///
+    /// Returns what the dry run reported the flip would run.
+    pub async fn flip(&mut self, signer: &Keypair) -> Result<FlipOutcome, Error> {
+        let mut call_builder = ink_e2e::create_call_builder::<CrossContractFlipper>(self.contract);
+        let message = call_builder.call_delegate_flip();
+        let mut call = self.client.call(signer, &message);
+        let outcome = call
+            .dry_run()
+            .await?
+            .message_result()
+            .map_err(Error::Dispatch)?
+            .map_err(Error::Contract)?;
+        call.submit().await?;
+        Ok(outcome)
+    }
+
+    /// Estimates what `flip` would cost, without submitting anything.
+    pub async fn estimate_flip(&mut self, signer: &Keypair) -> Result<Estimate, Error> {
+        let mut call_builder = ink_e2e::create_call_builder::<CrossContractFlipper>(self.contract);
+        let message = call_builder.call_delegate_flip();
+        let dry_run = self.client.call(signer, &message).dry_run().await?;
+        dry_run
+            .message_result()
+            .map_err(Error::Dispatch)?
+            .map_err(Error::Contract)?;
+        Ok(Estimate::of(&dry_run))
+    }
+
+    /// Returns the current value, read with a dry run on behalf of `origin`.
+    pub async fn get(&mut self, origin: &Keypair) -> Result<bool, Error> {
+        let call_builder = ink_e2e::create_call_builder::<CrossContractFlipper>(self.contract);
+        let message = call_builder.get();
+        let dry_run = self.client.call(origin, &message).dry_run().await?;
+        dry_run.message_result().map_err(Error::Dispatch)
+    }
+
+    /// Points the contract at a new delegate code hash, signed by `signer`.
+    ///
+    /// `signer` has to be the contract's owner.
+    pub async fn set_delegate(&mut self, signer: &Keypair, code_hash: Hash) -> Result<(), Error> {
+        let mut call_builder = ink_e2e::create_call_builder::<CrossContractFlipper>(self.contract);
+        let message = call_builder.set_delegate_to(code_hash);
+        let mut call = self.client.call(signer, &message);
+        call.dry_run()
+            .await?
+            .message_result()
+            .map_err(Error::Dispatch)?
+            .map_err(Error::Contract)?;
+        call.submit().await?;
+        Ok(())
+    }
+
+    /// Estimates what `set_delegate` would cost, without submitting anything.
+    pub async fn estimate_set_delegate(
+        &mut self,
+        signer: &Keypair,
+        code_hash: Hash,
+    ) -> Result<Estimate, Error> {
+        let mut call_builder = ink_e2e::create_call_builder::<CrossContractFlipper>(self.contract);
+        let message = call_builder.set_delegate_to(code_hash);
+        let dry_run = self.client.call(signer, &message).dry_run().await?;
+        dry_run
+            .message_result()
+            .map_err(Error::Dispatch)?
+            .map_err(Error::Contract)?;
+        Ok(Estimate::of(&dry_run))
+    }
+
+    /// Follows finalized blocks and calls `on_event` for every event the
+    /// contract emits, until the subscription ends.
+    pub async fn watch_events(&self, mut on_event: impl FnMut(ContractEvent)) -> Result<(), Error> {
+        let mut blocks = self
+            .api
+            .blocks()
+            .subscribe_finalized()
+            .await
+            .map_err(Error::Events)?;
+        while let Some(block) = blocks.next().await {
+            let block = block.map_err(Error::Events)?;
+            let events = block.events().await.map_err(Error::Events)?;
+            for event in events.iter() {
+                let event = event.map_err(Error::Events)?;
+                if event.pallet_name() != "Contracts" || event.variant_name() != "ContractEmitted" {
+                    continue;
+                }
+                let Ok((contract, data)) = <(AccountId, Vec<u8>)>::decode(&mut event.field_bytes())
+                else {
+                    continue;
+                };
+                if contract != self.contract {
+                    continue;
+                }
+                on_event(ContractEvent {
+                    block: block.number().into(),
+                    topics: event.topics().iter().map(|topic| topic.0).collect(),
+                    data,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name the `ink_e2e` contract registry knows the blob at `wasm` by.
+fn registry_name(wasm: &Path) -> String {
+    wasm.file_stem()
+        .map(|stem| stem.to_string_lossy().replace('-', "_"))
+        .unwrap_or_default()
+}
@@ -0,0 +1,241 @@
+//! Command line interface to deploy and interact with a `CrossContractFlipper`.
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use cross_contract_flipper::client::{to_hex, Estimate, FlipperClient};
+use ink::primitives::{AccountId, Hash};
+use ink_e2e::{
+    subxt::utils::AccountId32,
+    subxt_signer::{sr25519::Keypair, SecretUri},
+};
+use serde_json::{json, Value};
+use std::{fmt::Display, path::PathBuf, process::ExitCode, str::FromStr};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Websocket URL of the node.
+    #[arg(long, global = true, default_value = "ws://127.0.0.1:9944")]
+    url: String,
+    /// Secret URI of the signing account, e.g. `//Alice` or a mnemonic.
+    #[arg(
+        long,
+        global = true,
+        default_value = "//Alice",
+        conflicts_with = "keystore"
+    )]
+    suri: String,
+    /// File holding the secret URI of the signing account, such as a
+    /// Substrate keystore entry.
+    #[arg(long, global = true)]
+    keystore: Option<PathBuf>,
+    /// Dry-runs `deploy`, `flip` and `set-delegate` and prints what they
+    /// need instead of submitting them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Format of everything printed, including errors.
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    /// Human readable lines.
+    Text,
+    /// One JSON value per result, for scripts.
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Instantiates a new flipper from its Wasm blob.
+    Deploy {
+        /// Path to the flipper's `.wasm` file, as built by `cargo contract build`.
+        #[arg(long)]
+        wasm: PathBuf,
+        /// Code hash of the uploaded contract to delegate to.
+        #[arg(long, value_parser = parse_hash)]
+        code_hash: Hash,
+        /// Initial value.
+        #[arg(long)]
+        init_value: bool,
+    },
+    /// Flips the value through the delegate.
+    Flip(ContractArg),
+    /// Prints the current value.
+    Get(ContractArg),
+    /// Points the flipper at a new delegate code hash.
+    SetDelegate {
+        #[command(flatten)]
+        contract: ContractArg,
+        /// Code hash of the uploaded contract to delegate to.
+        #[arg(long, value_parser = parse_hash)]
+        code_hash: Hash,
+    },
+    /// Prints the flipper's raw events from finalized blocks as they come in.
+    Events(ContractArg),
+    /// Prints the flipper's decoded events from finalized blocks as they
+    /// come in, one line each.
+    Watch(ContractArg),
+}
+
+#[derive(Args)]
+struct ContractArg {
+    /// SS58 address of the deployed flipper.
+    #[arg(long, value_parser = parse_account)]
+    contract: AccountId,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let output = cli.output;
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match output {
+                Output::Text => eprintln!("Error: {err:#}"),
+                Output::Json => println!("{}", json!({ "error": format!("{err:#}") })),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let signer = signer(&cli)?;
+    let output = cli.output;
+
+    match cli.command {
+        Command::Deploy {
+            wasm,
+            code_hash,
+            init_value,
+        } => {
+            if cli.dry_run {
+                let estimate =
+                    FlipperClient::estimate_deploy(&cli.url, &signer, &wasm, init_value, code_hash)
+                        .await?;
+                print_estimate(output, estimate);
+                return Ok(());
+            }
+            let client =
+                FlipperClient::deploy(&cli.url, &signer, &wasm, init_value, code_hash).await?;
+            let contract = format_account(client.contract());
+            print(output, &contract, json!({ "contract": contract }));
+        }
+        Command::Flip(ContractArg { contract }) => {
+            let mut client = FlipperClient::connect(&cli.url, contract).await?;
+            if cli.dry_run {
+                print_estimate(output, client.estimate_flip(&signer).await?);
+                return Ok(());
+            }
+            client.flip(&signer).await?;
+            let value = client.get(&signer).await?;
+            print(output, value, json!({ "value": value }));
+        }
+        Command::Get(ContractArg { contract }) => {
+            let mut client = FlipperClient::connect(&cli.url, contract).await?;
+            let value = client.get(&signer).await?;
+            print(output, value, json!({ "value": value }));
+        }
+        Command::SetDelegate {
+            contract: ContractArg { contract },
+            code_hash,
+        } => {
+            let mut client = FlipperClient::connect(&cli.url, contract).await?;
+            if cli.dry_run {
+                print_estimate(
+                    output,
+                    client.estimate_set_delegate(&signer, code_hash).await?,
+                );
+                return Ok(());
+            }
+            client.set_delegate(&signer, code_hash).await?;
+            let code_hash = to_hex(code_hash.as_ref());
+            print(output, &code_hash, json!({ "delegate_to": code_hash }));
+        }
+        Command::Events(ContractArg { contract }) => {
+            let client = FlipperClient::connect(&cli.url, contract).await?;
+            client
+                .watch_events(|event| {
+                    let topics: Vec<_> = event.topics.iter().map(|topic| to_hex(topic)).collect();
+                    let text = format!(
+                        "#{} topics=[{}] data={}",
+                        event.block,
+                        topics.join(", "),
+                        to_hex(&event.data)
+                    );
+                    print(output, text, json!(event));
+                })
+                .await?;
+        }
+        Command::Watch(ContractArg { contract }) => {
+            let client = FlipperClient::connect(&cli.url, contract).await?;
+            client
+                .watch_events(|event| {
+                    let decoded = event.decode();
+                    let text = match &decoded {
+                        Some(decoded) => format!("#{} {decoded}", event.block),
+                        None => format!("#{} unknown data={}", event.block, to_hex(&event.data)),
+                    };
+                    print(
+                        output,
+                        text,
+                        json!({ "block": event.block, "event": decoded, "data": to_hex(&event.data) }),
+                    );
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a result as `text` or as `json`, depending on `output`.
+fn print(output: Output, text: impl Display, json: Value) {
+    match output {
+        Output::Text => println!("{text}"),
+        Output::Json => println!("{json}"),
+    }
+}
+
+/// Reads the signing key from `--keystore` if given, otherwise from `--suri`.
+fn signer(cli: &Cli) -> Result<Keypair> {
+    let suri = match &cli.keystore {
+        // Keystore entries hold the secret as a JSON string.
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("reading {}", path.display()))?
+            .trim()
+            .trim_matches('"')
+            .to_owned(),
+        None => cli.suri.clone(),
+    };
+    let uri = SecretUri::from_str(&suri).map_err(|err| anyhow!("invalid secret URI: {err}"))?;
+    Keypair::from_uri(&uri).map_err(|err| anyhow!("invalid secret URI: {err}"))
+}
+
+fn print_estimate(output: Output, estimate: Estimate) {
+    let text = format!(
+        "ref_time:        {}\nproof_size:      {}\nstorage deposit: {}",
+        estimate.ref_time, estimate.proof_size, estimate.storage_deposit
+    );
+    print(output, text, json!(estimate));
+}
+
+fn parse_account(s: &str) -> Result<AccountId> {
+    let account = AccountId32::from_str(s).map_err(|err| anyhow!("invalid address: {err:?}"))?;
+    Ok(AccountId::from(account.0))
+}
+
+fn format_account(account: AccountId) -> String {
+    AccountId32::from(*AsRef::<[u8; 32]>::as_ref(&account)).to_string()
+}
+
+fn parse_hash(s: &str) -> Result<Hash> {
+    let bytes: [u8; 32] = hex::decode(s.trim_start_matches("0x"))?
+        .try_into()
+        .map_err(|_| anyhow!("a code hash is 32 bytes long"))?;
+    Ok(Hash::from(bytes))
+}
@@ -0,0 +1,85 @@
+//! Storage shared by the cross-contract flipper and the logic contracts it
+//! delegates to.
+//!
+//! A delegate call runs the logic contract's code on the delegator's storage,
+//! so both have to agree on what is kept where. Every contract of a chain
+//! embeds `FlipperRoot` as its root cell and reserves the keys below, so the
+//! compiler rather than a convention keeps their layouts in line.
+//!
+//! The keys from `DELEGATOR_KEYS_START` up belong to the delegator's own
+//! bookkeeping, everything below to the logic contracts. By convention,
+//! logic contracts pick the keys of their fields with `logic_key` and the
+//! delegator those of its fields with `delegator_key`; both fail to compile
+//! if a key lands in the other's range. A logic contract that follows the
+//! convention doesn't touch the delegator's state, but nothing stops its
+//! code from writing any key of the storage it runs on, so the delegator
+//! only delegates to approved code hashes.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
+
+use ink::{env::hash::Blake2x256, primitives::Key};
+
+/// First storage key of the range reserved for the delegator.
+pub const DELEGATOR_KEYS_START: Key = 0xf000_0000;
+
+/// Returns the key `offset` into the delegator's range, e.g. for a
+/// `ManualKey<{ delegator_key(0) }>` field.
+pub const fn delegator_key(offset: u32) -> Key {
+    assert!(
+        offset <= Key::MAX - DELEGATOR_KEYS_START,
+        "offset leaves the delegator's key range"
+    );
+    DELEGATOR_KEYS_START + offset
+}
+
+/// Returns `key` for a logic contract to keep its own state at.
+pub const fn logic_key(key: Key) -> Key {
+    assert!(
+        key < DELEGATOR_KEYS_START,
+        "key is reserved for the delegator"
+    );
+    key
+}
+
+/// Storage key of the number of relays currently running in a delegation
+/// chain.
+///
+/// Reserved in the storage of every contract that delegates to a relay.
+pub const DEPTH_KEY: Key = logic_key(0xde1e_6a7e);
+
+/// The root cell of the delegator and of every logic contract it delegates
+/// to.
+///
+/// ink! reads the root cell on every message, so it only holds the value.
+/// Everything else the delegator keeps lives behind `Lazy` or `Mapping` at
+/// a `delegator_key`.
+#[ink::storage_item]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlipperRoot {
+    pub value: bool,
+}
+
+impl FlipperRoot {
+    /// Names the types of the fields, in order. Has to change along with
+    /// them, so delegators refuse logic contracts built against another
+    /// layout.
+    const LAYOUT: &'static str = "bool";
+
+    /// Returns the fingerprint of this layout at the root key `key`, which
+    /// logic contracts report as their `layout_hash`.
+    pub fn layout_hash(key: Key) -> [u8; 32] {
+        let mut output = [0; 32];
+        ink::env::hash_encoded::<Blake2x256, _>(&(key, Self::LAYOUT), &mut output);
+        output
+    }
+}
@@ -11,79 +11,7733 @@
 // `no_main` attribute disables the default entry point for the binary.
 // We define our own entry point using the `#[ink::contract]` attribute.
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
+// Panics fall back to the `unreachable` trap in release builds, so clippy
+// rejects what can panic outside of the tests. `panic!` itself is left to
+// `contracts_dont_panic`, since ink!'s expansions use it.
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
+
+#[cfg(feature = "client")]
+pub mod client;
+
+use ink::env::{DefaultEnvironment, Environment};
+
+/// Prints to the node's debug buffer when built with the `debug` feature,
+/// and compiles to nothing otherwise.
+///
+/// The buffer shows up in the output of dry runs against a dev node.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "debug")]
+        ink::env::debug_println!($($arg)*);
+    };
+}
+
+/// Chain extension giving access to the runtime's randomness source.
+#[ink::chain_extension(extension = 1)]
+pub trait FetchRandom {
+    type ErrorCode = RandomReadErr;
+
+    /// Returns 32 random bytes derived from `subject`.
+    #[ink(function = 1101)]
+    fn fetch_random(subject: [u8; 32]) -> [u8; 32];
+}
+
+/// Errors the randomness chain extension can return.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[ink::scale_derive(Encode, Decode, TypeInfo)]
+pub enum RandomReadErr {
+    FailGetRandomSource,
+    /// The chain extension returned a status code it doesn't document.
+    UnknownStatusCode(u32),
+}
+
+impl ink::env::chain_extension::FromStatusCode for RandomReadErr {
+    fn from_status_code(status_code: u32) -> Result<(), Self> {
+        match status_code {
+            0 => Ok(()),
+            1 => Err(Self::FailGetRandomSource),
+            code => Err(Self::UnknownStatusCode(code)),
+        }
+    }
+}
+
+/// The default environment extended with the `FetchRandom` chain extension.
+///
+/// Chains that use different primitive widths can select them with cargo
+/// features instead of forking the contract:
+///
+/// - `balance-u64`: `Balance` is a `u64` instead of a `u128`.
+/// - `block-number-u64`: `BlockNumber` is a `u64` instead of a `u32`.
+///
+/// `AccountId` and `Hash` stay the 32 byte types of the default environment,
+/// which every chain running `pallet-contracts` with XCM support uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[ink::scale_derive(TypeInfo)]
+pub enum FlipperEnvironment {}
+
+impl Environment for FlipperEnvironment {
+    const MAX_EVENT_TOPICS: usize = <DefaultEnvironment as Environment>::MAX_EVENT_TOPICS;
+
+    type AccountId = <DefaultEnvironment as Environment>::AccountId;
+    #[cfg(not(feature = "balance-u64"))]
+    type Balance = <DefaultEnvironment as Environment>::Balance;
+    #[cfg(feature = "balance-u64")]
+    type Balance = u64;
+    type Hash = <DefaultEnvironment as Environment>::Hash;
+    #[cfg(not(feature = "block-number-u64"))]
+    type BlockNumber = <DefaultEnvironment as Environment>::BlockNumber;
+    #[cfg(feature = "block-number-u64")]
+    type BlockNumber = u64;
+    type Timestamp = <DefaultEnvironment as Environment>::Timestamp;
+
+    type ChainExtension = FetchRandom;
+}
 
 /// Docs on utilities to call or instantiate contracts on the chain:
 /// https://docs.rs/ink_env/5.0.0/ink_env/call/index.html
 
-#[ink::contract]
+#[ink::contract(env = crate::FlipperEnvironment)]
 mod cross_contract_flipper {
+    use crate::RandomReadErr;
+    use flipper_storage::{delegator_key, FlipperRoot};
     use ink::{
         env::{
             call::{build_call, ExecutionInput, Selector},
-            CallFlags, DefaultEnvironment,
+            hash::Blake2x256,
+            CallFlags, Error as EnvError, ReturnErrorCode,
         },
+        prelude::{string::String, vec, vec::Vec},
+        scale::{Compact, Decode, Encode},
         storage::{traits::ManualKey, Lazy, Mapping},
+        xcm::{prelude::*, VersionedLocation, VersionedXcm},
     };
 
-    #[ink(storage)]
-    pub struct CrossContractFlipper {
-        value: bool,
-        delegate_to: Lazy<Hash>,
+    /// Errors that can occur upon calling this contract.
+    ///
+    /// Every variant has a stable numeric code, see `Error::code`, so
+    /// off-chain systems don't depend on the order of the variants.
+    #[derive(Debug, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum Error {
+        /// The caller is not the owner of this contract.
+        NotOwner,
+        /// The runtime rejected the dispatched call.
+        CallRuntimeFailed,
+        /// No XCM configuration has been set yet.
+        XcmConfigNotSet,
+        /// The XCM message could not be sent.
+        XcmSendFailed,
+        /// The XCM program failed to execute locally.
+        XcmExecutionFailed,
+        /// The runtime could not provide randomness.
+        RandomnessUnavailable,
+        /// The delegate call into the logic contract failed.
+        DelegateCallFailed,
+        /// No signer could be recovered from the signature.
+        InvalidSignature,
+        /// The signed payload was meant for another contract.
+        WrongContract,
+        /// The payload's nonce is not the signer's next nonce.
+        InvalidNonce,
+        /// The fee pool cannot cover the relayer fee.
+        InsufficientFeePool,
+        /// Transferring native tokens out of the contract failed.
+        TransferFailed,
+        /// The caller is not a registered session key of the account.
+        NotSessionKey,
+        /// The session key has expired.
+        SessionExpired,
+        /// The expiry block is not in the future.
+        InvalidExpiry,
+        /// The caller has an unrevealed commitment that is still open.
+        CommitmentPending,
+        /// The caller has no commitment to reveal.
+        NoCommitment,
+        /// The commitment's reveal window has passed.
+        CommitmentExpired,
+        /// The revealed value and salt don't match the commitment.
+        RevealMismatch,
+        /// No price oracle has been configured.
+        OracleNotSet,
+        /// Querying the price oracle failed.
+        OracleCallFailed,
+        /// The scheduled block is not in the future.
+        NotInFuture,
+        /// The schedule queue is full.
+        ScheduleFull,
+        /// No scheduled flip is due yet.
+        NothingDue,
+        /// The account flipped too recently and has to wait until `until`.
+        CooldownActive { until: BlockNumber },
+        /// The maximum number of flips for this block has been reached.
+        BlockFlipLimitReached,
+        /// Flipping requires an active subscription.
+        SubscriptionRequired,
+        /// Subscriptions are not offered.
+        SubscriptionsDisabled,
+        /// The transferred value doesn't cover a single period.
+        InsufficientPayment,
+        /// The given configuration is invalid.
+        InvalidConfig,
+        /// There is no auction taking bids.
+        AuctionNotActive,
+        /// The auction is still taking bids.
+        AuctionStillOpen,
+        /// The previous auction's winner hasn't claimed the flip yet.
+        AuctionUnsettled,
+        /// The bid doesn't beat the highest bid.
+        BidTooLow,
+        /// The caller didn't win the auction.
+        NotAuctionWinner,
+        /// The caller has no bid to reclaim.
+        NothingToReclaim,
+        /// No allowlist root has been configured.
+        AllowlistNotSet,
+        /// The proof doesn't show the caller is on the allowlist.
+        InvalidProof,
+        /// The caller is not a governance member.
+        NotMember,
+        /// There is no proposal with the given id.
+        ProposalNotFound,
+        /// The caller already voted on the proposal.
+        AlreadyVoted,
+        /// The proposal's voting period has ended.
+        VotingClosed,
+        /// The proposal's voting period hasn't ended yet.
+        VotingOpen,
+        /// The proposal's execution delay hasn't passed yet.
+        ExecutionDelayed,
+        /// The proposal didn't pass.
+        ProposalRejected,
+        /// The proposal has already been executed.
+        AlreadyExecuted,
+        /// The maximum number of governance members has been reached.
+        TooManyMembers,
+        /// Querying the governance token failed.
+        TokenQueryFailed,
+        /// A proposal has to carry between one and `MAX_PROPOSAL_ACTIONS`
+        /// actions.
+        InvalidProposal,
+        /// Flipping is paused.
+        Paused,
+        /// No other contract has been configured for queries.
+        OtherContractNotSet,
+        /// Querying the other contract failed or returned undecodable data.
+        QueryFailed,
+        /// A call in a multicall batch failed, so the whole batch reverted.
+        MulticallFailed { index: u32 },
+        /// The contract's balance doesn't cover the transfer.
+        InsufficientBalance,
+        /// The amount is below the chain's existential deposit, so it could
+        /// fail to endow a new account.
+        BelowMinimumBalance,
+        /// The account isn't a contract.
+        NotAContract,
+        /// The contract's code hash isn't the expected one.
+        CodeHashMismatch,
+        /// The code hash isn't on the list of approved logic contracts.
+        CodeHashNotApproved,
+        /// The contract can't delegate to its own code.
+        SelfDelegation,
+        /// Only accounts that signed the transaction themselves may flip.
+        CallerNotOrigin,
+        /// No selector is registered under the name.
+        SelectorNotRegistered,
+        /// The selector registry is full.
+        TooManySelectors,
+        /// Calling the other contract failed.
+        OtherCallFailed,
+        /// The delegation chain has more relays than `relay_contract::MAX_DEPTH`.
+        DelegateDepthExceeded,
+        /// No badge contract has been configured.
+        BadgeContractNotSet,
+        /// The badge contract refused to mint a badge.
+        BadgeMintFailed,
+        /// Flips with a referrer are not offered.
+        ReferralsDisabled,
+        /// The caller can't refer themselves.
+        SelfReferral,
+        /// The caller has no rewards to claim.
+        NothingToClaim,
+        /// The memo is longer than the configured `max_memo_len` bytes.
+        MemoTooLong,
+        /// More than `MAX_BATCH_ACCOUNTS` accounts were queried at once.
+        TooManyAccounts,
+        /// A batch has to flip between 1 and `MAX_BATCH_FLIPS` times.
+        InvalidFlipCount,
+        /// A flip has to hold for between 1 and `max_flip_ttl` blocks.
+        InvalidTtl,
+        /// Flips need the members' confirmations through `confirm_flip`.
+        ConfirmationRequired,
+        /// No confirmations are required, so there is nothing to confirm.
+        ConfirmationsDisabled,
+        /// The caller already confirmed the pending flip.
+        AlreadyConfirmed,
+        /// The caller isn't the guardian.
+        NotGuardian,
+        /// The latest flip is final or was vetoed already.
+        NothingToVeto,
+        /// The account was frozen by the owner.
+        AccountFrozen,
+        /// The instance flipped since it was created or imported into.
+        ImportClosed,
+        /// The state chunk couldn't be decoded.
+        InvalidStateChunk,
+        /// The state was migrated from a previous instance already.
+        AlreadyMigrated,
+        /// The contract has an owner already.
+        AlreadyInitialized,
+        /// No logic contract to delegate to was configured yet.
+        DelegateNotSet,
+        /// The runtime returned an error this contract doesn't expect.
+        RuntimeFailed,
+        /// An amount or an id would overflow.
+        Overflow,
+        /// The delegate's storage layout isn't the one this contract expects.
+        LayoutMismatch,
+        /// The logic contract doesn't report its storage layout.
+        LayoutUnknown,
+        /// The call at `index` returned more than `MAX_RETURN_DATA_LEN` bytes.
+        ReturnDataTooLarge { index: u32 },
+        /// The input of the call at `index` is longer than the configured
+        /// `max_input_len` bytes.
+        InputTooLarge { index: u32 },
+        /// A call out of the contract is still running.
+        Reentered,
+        /// Delegation was disabled for good.
+        DelegationDisabled,
+        /// No upgrade signers have been set yet.
+        UpgradeSignersNotSet,
+        /// The upgrade signers are more than `MAX_UPGRADE_SIGNERS`, repeat
+        /// an account or can't reach their threshold.
+        InvalidUpgradeSigners,
+        /// A signature isn't by one of the upgrade signers.
+        NotUpgradeSigner,
+        /// An upgrade signer signed more than once.
+        DuplicateSignature,
+        /// Fewer upgrade signers signed than their threshold.
+        ThresholdNotMet,
+        /// Only the new admin key can complete a key rotation.
+        NotNewAdmin,
+        /// The proposal was revoked when its proposer's key was rotated.
+        ProposalRevoked,
+        /// The message was switched off by the owner.
+        MessageDisabled,
+        /// The auction's winner can still claim the flip.
+        ClaimPeriodOpen,
+        /// The closed auction has no unclaimed winning bid.
+        NothingToSettle,
+        /// The caller has `MAX_SCHEDULED_FLIPS_PER_ACCOUNT` flips pending.
+        TooManyScheduledFlips,
     }
 
-    impl CrossContractFlipper {
-        /// Creates a new delegator smart contract with an initial value,
-        /// and the code hash of the contract it will delegate to
+    /// Type alias for the contract's result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Totals across all accounts, wide enough that busy instances don't
+    /// run out. Counters of a single account stay `u64`.
+    pub type Counter = u128;
+
+    impl Error {
+        /// Returns the stable code of this error.
         ///
-        /// Additionally, this code hash will be locked to prevent its deletion
-        /// because it is a dependency of this contract.
-        #[ink(constructor)]
-        pub fn new(init_value: bool, code_hash: Hash) -> Self {
-            let mut delegate_to = Lazy::new();
-            delegate_to.set(&code_hash);
+        /// New variants get the next free code; codes of removed variants
+        /// are never reused.
+        pub fn code(&self) -> u16 {
+            match self {
+                Error::NotOwner => 1,
+                Error::CallRuntimeFailed => 2,
+                Error::XcmConfigNotSet => 3,
+                Error::XcmSendFailed => 4,
+                Error::XcmExecutionFailed => 5,
+                Error::RandomnessUnavailable => 6,
+                Error::DelegateCallFailed => 7,
+                Error::InvalidSignature => 8,
+                Error::WrongContract => 9,
+                Error::InvalidNonce => 10,
+                Error::InsufficientFeePool => 11,
+                Error::TransferFailed => 12,
+                Error::NotSessionKey => 13,
+                Error::SessionExpired => 14,
+                Error::InvalidExpiry => 15,
+                Error::CommitmentPending => 16,
+                Error::NoCommitment => 17,
+                Error::CommitmentExpired => 18,
+                Error::RevealMismatch => 19,
+                Error::OracleNotSet => 20,
+                Error::OracleCallFailed => 21,
+                Error::NotInFuture => 22,
+                Error::ScheduleFull => 23,
+                Error::NothingDue => 24,
+                Error::CooldownActive { .. } => 25,
+                Error::BlockFlipLimitReached => 26,
+                Error::SubscriptionRequired => 27,
+                Error::SubscriptionsDisabled => 28,
+                Error::InsufficientPayment => 29,
+                Error::InvalidConfig => 30,
+                Error::AuctionNotActive => 31,
+                Error::AuctionStillOpen => 32,
+                Error::AuctionUnsettled => 33,
+                Error::BidTooLow => 34,
+                Error::NotAuctionWinner => 35,
+                Error::NothingToReclaim => 36,
+                Error::AllowlistNotSet => 37,
+                Error::InvalidProof => 38,
+                Error::NotMember => 39,
+                Error::ProposalNotFound => 40,
+                Error::AlreadyVoted => 41,
+                Error::VotingClosed => 42,
+                Error::VotingOpen => 43,
+                Error::ExecutionDelayed => 44,
+                Error::ProposalRejected => 45,
+                Error::AlreadyExecuted => 46,
+                Error::TooManyMembers => 47,
+                Error::TokenQueryFailed => 48,
+                Error::InvalidProposal => 49,
+                Error::Paused => 50,
+                Error::OtherContractNotSet => 51,
+                Error::QueryFailed => 52,
+                Error::MulticallFailed { .. } => 53,
+                Error::InsufficientBalance => 54,
+                Error::BelowMinimumBalance => 55,
+                Error::NotAContract => 56,
+                Error::CodeHashMismatch => 57,
+                Error::CodeHashNotApproved => 58,
+                Error::SelfDelegation => 59,
+                Error::CallerNotOrigin => 60,
+                Error::SelectorNotRegistered => 61,
+                Error::TooManySelectors => 62,
+                Error::OtherCallFailed => 63,
+                Error::DelegateDepthExceeded => 64,
+                Error::BadgeContractNotSet => 65,
+                Error::BadgeMintFailed => 66,
+                Error::ReferralsDisabled => 67,
+                Error::SelfReferral => 68,
+                Error::NothingToClaim => 69,
+                Error::MemoTooLong => 70,
+                Error::TooManyAccounts => 71,
+                Error::InvalidFlipCount => 72,
+                Error::InvalidTtl => 73,
+                Error::ConfirmationRequired => 74,
+                Error::ConfirmationsDisabled => 75,
+                Error::AlreadyConfirmed => 76,
+                Error::NotGuardian => 77,
+                Error::NothingToVeto => 78,
+                Error::AccountFrozen => 79,
+                Error::ImportClosed => 80,
+                Error::InvalidStateChunk => 81,
+                Error::AlreadyMigrated => 82,
+                Error::AlreadyInitialized => 83,
+                Error::DelegateNotSet => 84,
+                Error::RuntimeFailed => 85,
+                Error::Overflow => 86,
+                Error::LayoutMismatch => 87,
+                Error::LayoutUnknown => 88,
+                Error::ReturnDataTooLarge { .. } => 89,
+                Error::InputTooLarge { .. } => 90,
+                Error::Reentered => 91,
+                Error::DelegationDisabled => 92,
+                Error::UpgradeSignersNotSet => 93,
+                Error::InvalidUpgradeSigners => 94,
+                Error::NotUpgradeSigner => 95,
+                Error::DuplicateSignature => 96,
+                Error::ThresholdNotMet => 97,
+                Error::NotNewAdmin => 98,
+                Error::ProposalRevoked => 99,
+                Error::MessageDisabled => 100,
+                Error::ClaimPeriodOpen => 101,
+                Error::NothingToSettle => 102,
+                Error::TooManyScheduledFlips => 103,
+            }
+        }
+    }
+
+    impl From<EnvError> for Error {
+        fn from(e: EnvError) -> Self {
+            match e {
+                EnvError::ReturnError(ReturnErrorCode::CallRuntimeFailed) => {
+                    Error::CallRuntimeFailed
+                }
+                EnvError::ReturnError(ReturnErrorCode::XcmSendFailed) => Error::XcmSendFailed,
+                EnvError::ReturnError(ReturnErrorCode::TransferFailed) => Error::TransferFailed,
+                EnvError::ReturnError(ReturnErrorCode::XcmExecutionFailed) => {
+                    Error::XcmExecutionFailed
+                }
+                _ => Error::RuntimeFailed,
+            }
+        }
+    }
+
+    impl From<RandomReadErr> for Error {
+        fn from(_: RandomReadErr) -> Self {
+            Error::RandomnessUnavailable
+        }
+    }
+
+    /// A `MultiAddress` as understood by `pallet-balances`.
+    ///
+    /// Only the `Id` variant is needed here, so we avoid pulling in `sp-runtime`.
+    #[ink::scale_derive(Encode)]
+    pub enum MultiAddress {
+        #[codec(index = 0)]
+        Id(AccountId),
+    }
+
+    /// The subset of the runtime's `RuntimeCall` this contract dispatches.
+    ///
+    /// The pallet and call indices must match the runtime the contract is
+    /// deployed on; these are the ones used by `substrate-contracts-node`.
+    #[ink::scale_derive(Encode)]
+    enum RuntimeCall {
+        #[codec(index = 4)]
+        Balances(BalancesCall),
+    }
+
+    #[ink::scale_derive(Encode)]
+    enum BalancesCall {
+        #[codec(index = 3)]
+        TransferKeepAlive {
+            dest: MultiAddress,
+            #[codec(compact)]
+            value: Balance,
+        },
+    }
+
+    /// Weight limits for a call, split into its two dimensions.
+    ///
+    /// A dimension left at zero isn't limited, like in ink!'s call builder.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct WeightLimits {
+        pub ref_time: u64,
+        pub proof_size: u64,
+    }
+
+    impl From<WeightLimits> for Weight {
+        fn from(limits: WeightLimits) -> Self {
+            Weight::from_parts(limits.ref_time, limits.proof_size)
+        }
+    }
+
+    /// How `xcm_flip` reaches `pallet-contracts` on the destination chain.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct XcmConfig {
+        /// Index of `pallet-contracts` in the destination runtime.
+        pub contracts_pallet_index: u8,
+        /// Index of `call` within `pallet-contracts`.
+        pub call_index: u8,
+        /// Gas limit handed to the remote flipper.
+        pub gas_limit: WeightLimits,
+        /// Upper bound for the weight of the `Transact` instruction.
+        pub transact_weight: WeightLimits,
+        /// Amount of the destination's native asset withdrawn from this
+        /// contract's sovereign account to buy execution.
+        ///
+        /// If zero, the message is sent as `UnpaidExecution`.
+        pub fee: u128,
+        /// Para id of the chain this contract lives on, used to address this
+        /// contract's sovereign account on the destination.
+        pub self_para_id: u32,
+    }
+
+    /// What a signer authorizes when handing a flip to a relayer.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct FlipPayload {
+        /// The contract the flip is meant for, so the signature can't be
+        /// used against another instance.
+        pub contract: AccountId,
+        /// Must equal the signer's current `nonce_of`, so every signature
+        /// can be used exactly once and in order.
+        pub nonce: u64,
+        /// Lets relayers submit the flip again if they don't know whether it
+        /// went through: as long as one of the latest `IDEMPOTENCY_KEYS`
+        /// signed flips used the signer's key, it isn't executed twice.
+        pub idempotency_key: Option<[u8; 32]>,
+    }
+
+    /// Accounts that can authorize a delegate change together, as
+    /// returned by `upgrade_signers`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct UpgradeSigners {
+        pub signers: Vec<AccountId>,
+        /// Number of the signers that have to sign.
+        pub threshold: u32,
+    }
+
+    impl UpgradeSigners {
+        fn validate(&self) -> Result<()> {
+            let repeated = self.signers.iter().enumerate().any(|(index, signer)| {
+                self.signers
+                    .iter()
+                    .take(index)
+                    .any(|earlier| earlier == signer)
+            });
+            if self.signers.len() > MAX_UPGRADE_SIGNERS
+                || repeated
+                || self.threshold == 0
+                || self.threshold as usize > self.signers.len()
+            {
+                return Err(Error::InvalidUpgradeSigners);
+            }
+            Ok(())
+        }
+    }
 
-            Self::env().lock_delegate_dependency(&code_hash);
+    /// What the upgrade signers authorize, hashed by `upgrade_action_hash`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct UpgradeAction {
+        /// The contract the change is meant for, so the signatures can't be
+        /// used against another instance.
+        pub contract: AccountId,
+        /// The new delegate.
+        pub code_hash: Hash,
+        /// Must equal the current `upgrade_nonce`, so every set of
+        /// signatures can be used exactly once.
+        pub nonce: u64,
+    }
+
+    /// One upgrade signer's signature over an `upgrade_action_hash`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum UpgradeSignature {
+        /// By the sr25519 account `signer`.
+        Sr25519 {
+            signer: AccountId,
+            signature: [u8; 64],
+        },
+        /// A recoverable secp256k1 signature, by the account derived from
+        /// the recovered public key.
+        Ecdsa([u8; 65]),
+    }
+
+    /// What the current owner authorizes when handing the contract to a new
+    /// key, hashed by `key_rotation_hash`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct KeyRotation {
+        pub contract: AccountId,
+        pub old: AccountId,
+        pub new: AccountId,
+        /// Must equal the number of rotations so far, so a proof can't be
+        /// used again once the key was rotated back.
+        pub nonce: u64,
+    }
+
+    /// What an `AuditLog` event records.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum AuditKind {
+        /// An administrative message ran; the data is the SCALE encoded
+        /// caller and selector of the message.
+        Admin,
+        /// The contract called out of itself; the data is the SCALE encoded
+        /// selector of the call and whether it succeeded.
+        CrossCall,
+    }
+
+    /// Emitted for every administrative message and every call out of the
+    /// contract, numbered without gaps, so indexers can tell when they
+    /// missed one.
+    #[ink(event)]
+    pub struct AuditLog {
+        /// One more than the previous `AuditLog`'s, starting at 0.
+        pub seq: u64,
+        #[ink(topic)]
+        pub kind: AuditKind,
+        pub data: Vec<u8>,
+    }
+
+    /// Emitted when the owner's key was rotated.
+    #[ink(event)]
+    pub struct KeyRotated {
+        #[ink(topic)]
+        pub old: AccountId,
+        #[ink(topic)]
+        pub new: AccountId,
+        /// Scheduled flips of the old key that were dropped.
+        pub dropped_flips: u32,
+    }
+
+    /// Emitted whenever the value was flipped on someone's behalf.
+    #[ink(event)]
+    pub struct Flipped {
+        /// The account the flip is attributed to.
+        #[ink(topic)]
+        pub by: AccountId,
+        /// The value after the flip.
+        pub value: bool,
+        /// Data the flipper attached to correlate the flip off-chain.
+        pub memo: Option<Vec<u8>>,
+    }
+
+    /// Emitted once for all flips of a batch, instead of a `Flipped` event
+    /// per flip.
+    #[ink(event)]
+    pub struct FlippedBatch {
+        #[ink(topic)]
+        pub by: AccountId,
+        pub count: u32,
+        /// The value after the first flip of the batch.
+        pub first_value: bool,
+        /// The value after the last flip of the batch.
+        pub last_value: bool,
+    }
+
+    /// A secondary key allowed to flip on an account's behalf.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct SessionKey {
+        /// The hot key that may call `flip_as`.
+        pub key: AccountId,
+        /// The first block at which the key is no longer valid.
+        pub expires_at: BlockNumber,
+    }
+
+    /// Emitted when an account registers a session key.
+    #[cfg(feature = "meta-tx")]
+    #[ink(event)]
+    pub struct SessionKeyRegistered {
+        #[ink(topic)]
+        pub account: AccountId,
+        #[ink(topic)]
+        pub key: AccountId,
+        pub expires_at: BlockNumber,
+    }
+
+    /// Emitted when an account revokes its session key.
+    #[cfg(feature = "meta-tx")]
+    #[ink(event)]
+    pub struct SessionKeyRevoked {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    /// A hidden flip direction awaiting its reveal.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Commitment {
+        /// BLAKE2-256 hash of the SCALE encoded `(value, salt)`.
+        pub hash: Hash,
+        /// The first block at which the commitment can no longer be revealed.
+        pub expires_at: BlockNumber,
+    }
+
+    /// A flip waiting for its block to be executed by a keeper.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct ScheduledFlip {
+        pub id: u64,
+        /// The account the flip is attributed to.
+        pub by: AccountId,
+        /// The block from which on the flip can be executed.
+        pub at: BlockNumber,
+        /// Paid by `by` when scheduling, the bounty of the keeper executing
+        /// the flip.
+        pub deposit: Balance,
+    }
+
+    /// Upper bound for the number of pending scheduled flips, so `poke`
+    /// stays within a block's weight.
+    const MAX_SCHEDULED_FLIPS: usize = 32;
+
+    /// Upper bound for the number of pending scheduled flips per account, so
+    /// no account can fill the schedule.
+    const MAX_SCHEDULED_FLIPS_PER_ACCOUNT: usize = 4;
+
+    /// Emitted when a flip was scheduled.
+    #[ink(event)]
+    pub struct FlipScheduled {
+        pub id: u64,
+        #[ink(topic)]
+        pub by: AccountId,
+        pub at: BlockNumber,
+    }
+
+    /// Emitted for every due scheduled flip that failed and was dropped,
+    /// with the code of its error.
+    #[ink(event)]
+    pub struct ScheduledFlipFailed {
+        pub id: u64,
+        #[ink(topic)]
+        pub by: AccountId,
+        pub code: u16,
+    }
 
+    /// Emitted when a keeper was paid for executing due flips.
+    #[ink(event)]
+    pub struct KeeperPaid {
+        #[ink(topic)]
+        pub keeper: AccountId,
+        pub executed: u32,
+        pub bounty: Balance,
+    }
+
+    /// Emitted when an account bought or extended a subscription.
+    #[ink(event)]
+    pub struct Subscribed {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub expires_at: BlockNumber,
+    }
+
+    /// An auction for the right to perform the next flip.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Auction {
+        pub id: u32,
+        /// The first block at which no more bids are accepted.
+        pub ends_at: BlockNumber,
+        /// The current highest bidder and the total they have bid.
+        pub highest: Option<(AccountId, Balance)>,
+        /// Whether the winner has performed their flip.
+        pub claimed: bool,
+    }
+
+    /// Emitted when an account bid in the running auction.
+    #[cfg(feature = "auctions")]
+    #[ink(event)]
+    pub struct BidPlaced {
+        pub auction: u32,
+        #[ink(topic)]
+        pub bidder: AccountId,
+        pub total: Balance,
+    }
+
+    /// Emitted when an auction was settled without its winner's flip.
+    #[cfg(feature = "auctions")]
+    #[ink(event)]
+    pub struct AuctionSettled {
+        pub auction: u32,
+        #[ink(topic)]
+        pub winner: AccountId,
+        /// The winning bid, credited to the fee pool.
+        pub bid: Balance,
+    }
+
+    /// Number of blocks after an auction closed in which only its winner
+    /// can settle it, by claiming the flip.
+    #[cfg(feature = "auctions")]
+    const AUCTION_CLAIM_PERIOD: BlockNumber = 100;
+
+    /// A flip collecting the members' confirmations.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct PendingConfirmation {
+        pub id: u32,
+        /// The first block at which no more confirmations are accepted.
+        pub expires_at: BlockNumber,
+        pub confirmations: u32,
+    }
+
+    /// Emitted when a member confirmed the pending flip.
+    #[cfg(feature = "governance")]
+    #[ink(event)]
+    pub struct FlipConfirmed {
+        #[ink(topic)]
+        pub id: u32,
+        #[ink(topic)]
+        pub by: AccountId,
+        /// Confirmations so far, including this one.
+        pub confirmations: u32,
+    }
+
+    /// Emitted when a pending flip ran out of time before it had enough
+    /// confirmations.
+    #[cfg(feature = "governance")]
+    #[ink(event)]
+    pub struct ConfirmationExpired {
+        #[ink(topic)]
+        pub id: u32,
+        pub confirmations: u32,
+    }
+
+    /// What an approved proposal does once executed.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum GovernanceAction {
+        /// Delegate to the logic contract with the given code hash.
+        SetDelegate(Hash),
+        /// Replace the governance parameters.
+        SetGovernanceConfig(GovernanceConfig),
+        /// Pause flipping.
+        Pause,
+        /// Resume flipping.
+        Unpause,
+        /// Set the fee paid to relayers of signed flips.
+        SetRelayerFee(Balance),
+    }
+
+    /// Parameters of the governance process.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct GovernanceConfig {
+        /// Share of the voting weight, in percent, that has to take part in
+        /// a vote for it to count.
+        pub quorum_percent: u8,
+        /// Number of blocks members can vote on a proposal.
+        pub voting_period: BlockNumber,
+        /// Number of blocks between the end of the vote and the earliest
+        /// execution of a passed proposal.
+        pub execution_delay: BlockNumber,
+    }
+
+    impl Default for GovernanceConfig {
+        fn default() -> Self {
             Self {
-                value: init_value,
-                delegate_to,
+                quorum_percent: 50,
+                voting_period: 100,
+                execution_delay: 0,
             }
         }
+    }
 
-        // Call 'flip' method of the other contract using delegate call
-        #[ink(message)]
-        pub fn call_delegate_flip(&mut self) {
-            let selector = ink::selector_bytes!("flip");
-            let _ = build_call::<DefaultEnvironment>()
-                .delegate(self.delegate_to())
-                .call_flags(CallFlags::TAIL_CALL)
-                .exec_input(ExecutionInput::new(Selector::new(selector)))
-                .returns::<()>()
-                .try_invoke();
+    #[cfg(feature = "governance")]
+    impl GovernanceConfig {
+        fn validate(&self) -> Result<()> {
+            if !(1..=100).contains(&self.quorum_percent) || self.voting_period == 0 {
+                return Err(Error::InvalidConfig);
+            }
+            Ok(())
+        }
+    }
+
+    /// A governance proposal put to a vote by the members.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Proposal {
+        pub proposer: AccountId,
+        /// Executed in order, all or nothing.
+        pub actions: Vec<GovernanceAction>,
+        /// The first block at which no more votes are accepted.
+        pub ends_at: BlockNumber,
+        /// Total voting weight of the members when the proposal was created.
+        pub electorate: u128,
+        /// Voting weight that has to take part for the vote to count.
+        pub quorum: u128,
+        /// The first block at which the proposal can be executed.
+        pub executable_at: BlockNumber,
+        pub votes_for: u128,
+        pub votes_against: u128,
+        pub executed: bool,
+    }
+
+    /// Upper bound for the number of governance members, so their voting
+    /// weights can be snapshotted within a single `propose`.
+    const MAX_MEMBERS: usize = 32;
+
+    /// Upper bound for the number of actions a single proposal carries.
+    #[cfg(feature = "governance")]
+    const MAX_PROPOSAL_ACTIONS: usize = 8;
+
+    /// Number of accounts on the leaderboard, small enough to re-sort on
+    /// every flip.
+    const LEADERBOARD_SIZE: usize = 10;
+
+    /// An account on the leaderboard and its score.
+    type LeaderboardEntry = (AccountId, u64);
+
+    /// Number of the latest flips kept in the history. Older ones are
+    /// overwritten.
+    const HISTORY_SIZE: u64 = 256;
+
+    /// Upper bound for the number of records in a history page.
+    const MAX_HISTORY_PAGE: u32 = 32;
+
+    /// Upper bound for the number of flips `flip_n` makes at once.
+    const MAX_BATCH_FLIPS: u32 = 16;
+
+    /// Upper bound for the number of accounts `get_many` returns or
+    /// `batch_set` sets at once.
+    const MAX_BATCH_ACCOUNTS: usize = 32;
+
+    /// Upper bound for the length of a flip's memo, in bytes, and the
+    /// default `max_memo_len`.
+    const MAX_MEMO_LEN: usize = 64;
+
+    /// Upper bound for the configured `max_input_len`, in bytes.
+    const MAX_INPUT_LEN: u32 = 4096;
+
+    /// Default `max_input_len`, in bytes.
+    const DEFAULT_MAX_INPUT_LEN: u32 = 1024;
+
+    /// Upper bound for the number of badge milestones.
+    const MAX_BADGE_MILESTONES: usize = 16;
+
+    /// Index of the `Bytes` variant of PSP34's `Id`, which badge ids use.
+    const PSP34_ID_BYTES: u8 = 5;
+
+    /// Number of the latest idempotency keys of signed flips that are
+    /// remembered, whoever signed them.
+    #[cfg(feature = "meta-tx")]
+    const IDEMPOTENCY_KEYS: u32 = 64;
+
+    /// A signer and the idempotency key of one of its signed flips.
+    type IdempotencyKey = (AccountId, [u8; 32]);
+
+    /// Upper bound for the number of upgrade signers.
+    const MAX_UPGRADE_SIGNERS: usize = 16;
+
+    /// Upper bound for the number of names in the selector registry, so
+    /// tooling can list them in a single query.
+    const MAX_SELECTORS: usize = 64;
+
+    /// Upper bound for the length of the encoded data a call of `multicall`
+    /// or `call_named` may return, in bytes, since it is copied into the
+    /// result and possibly re-emitted.
+    const MAX_RETURN_DATA_LEN: usize = 1024;
+
+    /// Size of the buffer the return data of a `multicall` call is read
+    /// into. Matches ink!'s default static buffer, so data that `try_invoke`
+    /// could read still gets to fail with `ReturnDataTooLarge`.
+    #[cfg(target_arch = "wasm32")]
+    const CALL_OUTPUT_BUFFER_LEN: usize = 16 * 1024;
+
+    /// Emitted when the delegate target changed.
+    #[ink(event)]
+    pub struct DelegateUpdated {
+        /// `None` if the contract didn't delegate before.
+        pub old: Option<Hash>,
+        pub new: Hash,
+    }
+
+    /// Emitted when delegation was disabled for good.
+    #[ink(event)]
+    pub struct DelegationDisabled {
+        /// The delegate at the time, if any.
+        pub last: Option<Hash>,
+    }
+
+    /// Emitted when `cleanup_dependencies` unlocked a code hash, or when
+    /// disabling delegation unlocked the delegate.
+    #[ink(event)]
+    pub struct DependencyUnlocked {
+        #[ink(topic)]
+        pub code_hash: Hash,
+    }
+
+    /// Emitted when a logic code hash was approved or revoked.
+    #[ink(event)]
+    pub struct CodeHashApproval {
+        #[ink(topic)]
+        pub code_hash: Hash,
+        pub approved: bool,
+    }
+
+    /// Emitted when the owner froze or unfroze an account.
+    #[ink(event)]
+    pub struct AccountFreeze {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub frozen: bool,
+    }
+
+    /// Emitted once for all values set by a `batch_set`.
+    #[ink(event)]
+    pub struct ValuesSet {
+        pub count: u32,
+        /// How many of the accounts were set to `true`.
+        pub set_true: u32,
+    }
+
+    /// Emitted when a member created a proposal.
+    #[cfg(feature = "governance")]
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        pub id: u32,
+        #[ink(topic)]
+        pub proposer: AccountId,
+        pub actions: Vec<GovernanceAction>,
+    }
+
+    /// Emitted when a member voted on a proposal.
+    #[cfg(feature = "governance")]
+    #[ink(event)]
+    pub struct Voted {
+        #[ink(topic)]
+        pub id: u32,
+        #[ink(topic)]
+        pub voter: AccountId,
+        pub support: bool,
+        pub weight: u128,
+    }
+
+    /// Emitted when an approved proposal was executed.
+    #[cfg(feature = "governance")]
+    #[ink(event)]
+    pub struct ProposalExecuted {
+        #[ink(topic)]
+        pub id: u32,
+    }
+
+    /// A pre-encoded message call, as executed by `multicall`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct EncodedCall {
+        pub target: AccountId,
+        pub selector: [u8; 4],
+        /// SCALE encoded arguments, appended to the selector as they are.
+        pub input: Vec<u8>,
+        /// Code hash `target` has to run, if it matters.
+        pub expected_code_hash: Option<Hash>,
+        /// Overrides the configured `call_limits` for this call.
+        pub limits: Option<WeightLimits>,
+    }
+
+    /// Arguments that are already SCALE encoded.
+    #[cfg(not(target_arch = "wasm32"))]
+    struct RawInput<'a>(&'a [u8]);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    impl ink::scale::Encode for RawInput<'_> {
+        fn size_hint(&self) -> usize {
+            self.0.len()
+        }
+
+        fn encode_to<T: ink::scale::Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// Makes `call` like `try_invoke` does, except that a callee which
+    /// reverted fails with `CalleeReverted`. ink_env 5.1 decodes the output
+    /// of a reverted callee as if the call had succeeded, and a message that
+    /// returns `Err` reverts with `Ok(Err(_))`.
+    #[cfg(target_arch = "wasm32")]
+    fn invoke_reverting(
+        call: &EncodedCall,
+        limits: WeightLimits,
+    ) -> core::result::Result<ink::MessageResult<RawOutput>, EnvError> {
+        use pallet_contracts_uapi::{HostFn, HostFnImpl};
+
+        let mut input = Vec::with_capacity(call.selector.len() + call.input.len());
+        input.extend_from_slice(&call.selector);
+        input.extend_from_slice(&call.input);
+        let value: Balance = 0;
+        let mut buffer = [0; CALL_OUTPUT_BUFFER_LEN];
+        let mut output = &mut buffer[..];
+        HostFnImpl::call_v2(
+            CallFlags::empty(),
+            &call.target.encode(),
+            limits.ref_time,
+            limits.proof_size,
+            None,
+            &value.encode(),
+            &input,
+            Some(&mut output),
+        )?;
+        Ok(ink::scale::DecodeAll::decode_all(&mut &output[..])?)
+    }
+
+    /// Return data that is kept encoded, whatever its type, up to
+    /// `MAX_RETURN_DATA_LEN` bytes.
+    #[derive(Debug, PartialEq, Eq)]
+    struct RawOutput(Vec<u8>);
+
+    impl ink::scale::Decode for RawOutput {
+        fn decode<I: ink::scale::Input>(
+            input: &mut I,
+        ) -> core::result::Result<Self, ink::scale::Error> {
+            let len = input
+                .remaining_len()?
+                .ok_or("length of the return data is unknown")?;
+            if len > MAX_RETURN_DATA_LEN {
+                return Err("return data exceeds MAX_RETURN_DATA_LEN".into());
+            }
+            let mut output = vec![0; len];
+            input.read(&mut output)?;
+            Ok(Self(output))
+        }
+    }
+
+    /// Outcomes of the outbound calls made with one selector.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct CallStats {
+        pub successes: u64,
+        pub failures: u64,
+    }
+
+    /// How `delegate_flip_with` calls the delegate.
+    ///
+    /// `e2e_delegate_modes_benchmark` prints the weight and storage deposit
+    /// of both modes on the node it runs against.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum DelegateMode {
+        /// A normal delegate call whose result is decoded, like
+        /// `call_delegate_flip` makes.
+        Decoded,
+        /// A delegate call with `TAIL_CALL`, which hands the delegate's
+        /// output straight to the caller. It weighs less, since nothing runs
+        /// after it, but the flip isn't attributed to the caller, no
+        /// `Flipped` event is emitted, the delegate's gas isn't measured and
+        /// no `FlipOutcome` is returned.
+        TailCall,
+    }
+
+    /// What `call_delegate_flip` ran, as returned by it and by
+    /// `delegate_flip_with`.
+    ///
+    /// Only the `Decoded` mode returns one. What it weighs more than the
+    /// `TailCall` mode is decoding the delegate's result, measuring its gas,
+    /// attributing the flip (the account's counters, the history, the epoch,
+    /// the score, the leaderboard and the streak) and the `Flipped` event.
+    /// The first time an account flips, it also pays the storage deposit of
+    /// the account's new entries in `flips_by`, `scores` and the epoch's
+    /// flips. `e2e_delegate_modes_benchmark` checks that the `TailCall` mode
+    /// costs less and prints both differences for the node it runs against.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct FlipOutcome {
+        /// Code hash of the delegate that flipped the value.
+        pub target: Hash,
+        /// Bits of the `CallFlags` the delegate call was made with, so never
+        /// `TAIL_CALL`.
+        pub flags: u32,
+        /// The value after the flip.
+        pub value: bool,
+        /// Gas (`ref_time`) the delegate's `flip` used.
+        pub gas_used: u64,
+    }
+
+    /// The delegate call `call_delegate_flip` makes, as returned by
+    /// `delegate_call_params`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct DelegateCallParams {
+        pub code_hash: Hash,
+        pub selector: [u8; 4],
+        /// Complete input of the call, selector included.
+        pub input: Vec<u8>,
+        /// Bits of the `CallFlags` the call is made with.
+        pub flags: u32,
+        /// Always `None`, since a delegate call can use all weight left.
+        pub limits: Option<WeightLimits>,
+    }
+
+    /// Overview of the contract, as returned by `contract_info`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct ContractInfo {
+        pub account_id: AccountId,
+        pub balance: Balance,
+        pub code_hash: Hash,
+        pub delegate_to: Option<Hash>,
+        pub owner: Option<AccountId>,
+        pub paused: bool,
+        pub flip_count: Counter,
+        /// See `is_entered`.
+        pub entered: bool,
+    }
+
+    /// Attributed flips of one epoch, as returned by `epoch_stats`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct EpochStats {
+        pub flips: u64,
+        /// Accounts with at least one flip in the epoch.
+        pub flippers: u64,
+    }
+
+    /// Flip statistics across all accounts, as returned by `global_stats`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct GlobalStats {
+        /// Flips executed through the delegate, attributed or not.
+        pub total_flips: Counter,
+        /// Flips attributed to an account.
+        pub attributed_flips: Counter,
+        /// Accounts with at least one attributed flip.
+        pub flippers: Counter,
+    }
+
+    /// `GlobalStats` as returned by instances of storage version 2 or
+    /// older, whose counters were `u64`.
+    #[ink::scale_derive(Decode)]
+    struct GlobalStatsV2 {
+        total_flips: u64,
+        attributed_flips: u64,
+        flippers: u64,
+    }
+
+    impl From<GlobalStatsV2> for GlobalStats {
+        fn from(stats: GlobalStatsV2) -> Self {
+            Self {
+                total_flips: stats.total_flips.into(),
+                attributed_flips: stats.attributed_flips.into(),
+                flippers: stats.flippers.into(),
+            }
+        }
+    }
+
+    /// The owner as returned by `owner`, which older instances returned
+    /// without the `Option`.
+    struct QueriedOwner(Option<AccountId>);
+
+    impl Decode for QueriedOwner {
+        fn decode<I: ink::scale::Input>(
+            input: &mut I,
+        ) -> core::result::Result<Self, ink::scale::Error> {
+            let owner = match input.remaining_len()? {
+                Some(32) => Some(AccountId::decode(input)?),
+                _ => Option::decode(input)?,
+            };
+            Ok(Self(owner))
+        }
+    }
+
+    /// Everything the contract keeps about one account, as returned by
+    /// `get_many`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct AccountState {
+        pub account: AccountId,
+        pub flips: u64,
+        pub nonce: u64,
+        pub last_flip_at: Option<BlockNumber>,
+        pub subscribed_until: Option<BlockNumber>,
+        pub referral_rewards: Balance,
+        pub value: bool,
+    }
+
+    /// An attributed flip, as kept in the history.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct FlipRecord {
+        pub block: BlockNumber,
+        pub by: AccountId,
+        /// The value after the flip.
+        pub value: bool,
+    }
+
+    /// Opaque position in the flip history, as returned by `flip_history`.
+    ///
+    /// Points at a flip rather than an offset, so it stays valid while flips
+    /// are appended.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct HistoryCursor(u64);
+
+    /// A page of the flip history.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct HistoryPage {
+        /// Oldest first.
+        pub records: Vec<FlipRecord>,
+        /// Where the next page starts, including flips made later.
+        pub next: HistoryCursor,
+    }
+
+    /// When a flip stops holding and which value the contract falls back to.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Expiry {
+        /// The first block at which the flip no longer holds.
+        pub at: BlockNumber,
+        pub reverts_to: bool,
+    }
+
+    /// How far the latest flip is from being final, as returned by
+    /// `last_flip_status`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum FlipStatus {
+        /// The guardian can still veto the flip.
+        Pending {
+            /// The first block at which the flip is final.
+            final_at: BlockNumber,
+        },
+        Final,
+        /// The guardian reverted the flip.
+        Vetoed,
+    }
+
+    /// The latest flip, as kept for the guardian's veto.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct LastFlip {
+        /// The value before the flip, restored by a veto.
+        pub before: bool,
+        pub status: FlipStatus,
+    }
+
+    /// Emitted when the guardian reverted the latest flip.
+    #[ink(event)]
+    pub struct FlipVetoed {
+        #[ink(topic)]
+        pub guardian: AccountId,
+        /// The value the flip was reverted to.
+        pub value: bool,
+    }
+
+    /// A run of consecutive attributed flips by the same account.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Streak {
+        pub account: AccountId,
+        pub length: u64,
+    }
+
+    /// A piece of the state moved between instances by `export_state` and
+    /// `import_state`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum StateItem {
+        /// The value and the global counters, always exported first.
+        ///
+        /// `flippers` is left out, since it has to match `flips_by`, which
+        /// only moves along with the history.
+        Core {
+            value: bool,
+            flip_count: Counter,
+            attributed_flips: Counter,
+            history_len: u64,
+        },
+        Member(AccountId),
+        Selector(String, [u8; 4]),
+        /// A flip of the history, its position in it and the number of flips
+        /// attributed to the account that made it.
+        History(u64, FlipRecord, u64),
+    }
+
+    /// Upper bound for the number of items in an exported state chunk.
+    const MAX_EXPORT_ITEMS: u32 = 32;
+
+    /// Version of the storage layout, bumped whenever it changes in a way
+    /// that needs a migration.
+    const STORAGE_VERSION: u16 = 4;
+
+    /// Upper bound for the number of the latest proposals
+    /// `validate_migration` looks at.
+    const MAX_REPORTED_PROPOSALS: u32 = 32;
+
+    /// What stands in the way of moving the state to a new instance, as
+    /// returned by `validate_migration`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct MigrationReport {
+        pub storage_version: u16,
+        /// BLAKE2-256 hash of the storage keys of all fields.
+        pub layout_hash: Hash,
+        /// Unexecuted proposals, among the latest `MAX_REPORTED_PROPOSALS`,
+        /// still being voted on or waiting for their execution delay.
+        pub pending_proposals: Vec<u32>,
+        pub scheduled_flips: u32,
+        /// Whether an auction is open or its winner hasn't flipped yet.
+        pub auction_pending: bool,
+        pub confirmation_pending: bool,
+        /// Whether the guardian can still veto the latest flip.
+        pub veto_pending: bool,
+        /// Whether the latest flip is yet to expire.
+        pub expiry_pending: bool,
+        /// The fee pool, the unclaimed referral rewards, the escrowed bids
+        /// and the deposits of scheduled flips.
+        pub owed: Balance,
+        pub paused: bool,
+        /// Whether flipping is paused and nothing is pending, so the state
+        /// can be moved without losing anything in flight.
+        pub ready: bool,
+    }
+
+    /// An inconsistency in the contract's state, as reported by
+    /// `check_invariants`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum Violation {
+        /// More flips were attributed than executed.
+        AttributedAheadOfTotal {
+            attributed_flips: Counter,
+            total_flips: Counter,
+        },
+        /// More accounts flipped than flips were attributed.
+        FlippersAheadOfFlips {
+            flippers: Counter,
+            attributed_flips: Counter,
+        },
+        /// More flips were added to the history than were attributed.
+        HistoryAheadOfFlips {
+            history_len: u64,
+            attributed_flips: Counter,
+        },
+        /// The leaderboard has more than `LEADERBOARD_SIZE` entries.
+        LeaderboardOverfull { len: u32 },
+        /// The leaderboard isn't ordered by score or lists an account twice.
+        LeaderboardUnsorted,
+        /// The leaderboard scores sum up to more than the attributed flips.
+        LeaderboardAheadOfFlips {
+            sum: Counter,
+            attributed_flips: Counter,
+        },
+        /// The lock of the generic forwarder is held between transactions.
+        LockHeld,
+    }
+
+    /// Versions of a deployed contract, as returned by `version`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct Version {
+        /// Semantic version of the crate the contract was built from.
+        pub contract: String,
+        pub storage: u16,
+    }
+
+    /// Number of blocks a commitment can be revealed in, unless configured.
+    const DEFAULT_REVEAL_WINDOW: BlockNumber = 100;
+
+    /// How to set up a new instance, as passed to `with_config` and
+    /// `initialize`.
+    #[derive(Debug, Clone, PartialEq, Eq, Default)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct InitConfig {
+        pub init_value: bool,
+        /// Logic contract to delegate to, approved right away. `None` leaves
+        /// delegation unset, like `new_default`.
+        pub code_hash: Option<Hash>,
+        /// `None` makes the caller the owner.
+        pub owner: Option<AccountId>,
+        /// Paid to the submitter of a signed flip out of the fee pool.
+        pub relayer_fee: Balance,
+    }
+
+    /// The contract's tunables, as returned by `get_config`.
+    ///
+    /// Kept in a single cell, since most messages read several of them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Config {
+        /// Paid to the submitter of a signed flip out of the fee pool.
+        pub relayer_fee: Balance,
+        /// The minimum deposit of a scheduled flip, which is paid to the
+        /// keeper executing it.
+        pub keeper_bounty: Balance,
+        /// Number of blocks a commitment can be revealed in.
+        pub reveal_window: BlockNumber,
+        /// Number of blocks an account has to wait between flips.
+        pub cooldown: BlockNumber,
+        /// Flips allowed per block across all accounts, zero for no limit.
+        pub max_flips_per_block: u32,
+        /// Number of blocks a flip holds, zero keeps flips for good.
+        pub flip_ttl: BlockNumber,
+        /// Longest number of blocks callers can make a flip hold.
+        pub max_flip_ttl: BlockNumber,
+        /// Number of blocks the guardian can veto a flip in.
+        pub veto_window: BlockNumber,
+        /// Number of blocks after which scores halve, zero for no decay.
+        pub decay_period: BlockNumber,
+        pub paused: bool,
+        /// Whether flips have to come from the transaction's origin.
+        pub origin_only: bool,
+        /// Longest input of a call of `multicall` or `call_named`, in bytes,
+        /// at most `MAX_INPUT_LEN`.
+        pub max_input_len: u32,
+        /// Longest memo of `flip_with_memo`, in bytes, at most
+        /// `MAX_MEMO_LEN`.
+        pub max_memo_len: u32,
+        /// Bits of the `MessageFlag`s that are switched off.
+        pub disabled_messages: u32,
+        /// Weight limits of the calls `multicall`, `call_named` and
+        /// `call_other_set` make unless their caller passes others, `None`
+        /// for all weight left. Delegate calls can't be limited.
+        pub call_limits: Option<WeightLimits>,
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Self {
+                relayer_fee: 0,
+                keeper_bounty: 0,
+                reveal_window: DEFAULT_REVEAL_WINDOW,
+                cooldown: 0,
+                max_flips_per_block: 0,
+                flip_ttl: 0,
+                max_flip_ttl: 0,
+                veto_window: 0,
+                decay_period: 0,
+                paused: false,
+                origin_only: false,
+                max_input_len: DEFAULT_MAX_INPUT_LEN,
+                max_memo_len: MAX_MEMO_LEN as u32,
+                disabled_messages: 0,
+                call_limits: None,
+            }
+        }
+    }
+
+    /// Groups of messages the owner can switch off one by one with
+    /// `set_message_enabled`, without pausing everything.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum MessageFlag {
+        /// `flip_with_signature`, `flip_with_sr25519` and `flip_as`.
+        MetaTx,
+        /// `multicall` and `multicall_best_effort`.
+        Multicall,
+        /// `call_named`.
+        CallNamed,
+        /// `xcm_flip` and `xcm_fund_and_flip`.
+        Xcm,
+        /// `flip_n`.
+        FlipN,
+    }
+
+    impl MessageFlag {
+        /// Returns the bit of this flag in `Config::disabled_messages`.
+        pub fn bit(self) -> u32 {
+            1 << self as u32
+        }
+    }
+
+    impl Config {
+        fn validate(&self) -> Result<()> {
+            if self.reveal_window == 0
+                || self.max_input_len > MAX_INPUT_LEN
+                || self.max_memo_len as usize > MAX_MEMO_LEN
+            {
+                return Err(Error::InvalidConfig);
+            }
+            Ok(())
+        }
+    }
+
+    /// How many of the latest delegate calls `avg_delegate_gas` roughly
+    /// averages over.
+    const DELEGATE_GAS_SMOOTHING: u64 = 8;
+
+    /// Weight a query may spend in the other contract.
+    const QUERY_LIMITS: WeightLimits = WeightLimits {
+        ref_time: 5_000_000_000,
+        proof_size: 256 * 1024,
+    };
+
+    /// Emitted when the submitter of a signed flip was paid for relaying it.
+    #[cfg(feature = "meta-tx")]
+    #[ink(event)]
+    pub struct RelayerPaid {
+        #[ink(topic)]
+        pub relayer: AccountId,
+        #[ink(topic)]
+        pub signer: AccountId,
+        pub fee: Balance,
+    }
+
+    /// Emitted when an account sent funds to the contract through `receive`.
+    #[ink(event)]
+    pub struct Received {
+        #[ink(topic)]
+        pub from: AccountId,
+        pub amount: Balance,
+    }
+
+    /// Emitted after every delegate call into the delegate's `flip` that
+    /// returned, with the gas (`ref_time`) it used.
+    #[ink(event)]
+    pub struct DelegateCalled {
+        #[ink(topic)]
+        pub target: Hash,
+        pub gas_used: u64,
+    }
+
+    /// Emitted for every call of `multicall_best_effort` that failed, with
+    /// the code of its error.
+    #[ink(event)]
+    pub struct CallFailed {
+        pub index: u32,
+        #[ink(topic)]
+        pub code: u16,
+    }
+
+    /// Note: the delegate (`other-contract`) reads and writes the root storage
+    /// cell as a `FlipperRoot`, so `root` must remain the only packed field.
+    /// Everything else is kept behind `Lazy` or `Mapping`.
+    ///
+    /// Every other field is pinned to a key in the delegator's range of
+    /// `flipper_storage`, where logic contracts don't put their state, in
+    /// the order the fields were added. Offsets of removed fields are never
+    /// reused.
+    ///
+    /// ink! reads the root cell on every message, so keeping it a single
+    /// byte also keeps that read as cheap as it gets. Settings read on every
+    /// flip are grouped into one `Lazy` cell, `config`, so the flip path
+    /// reads them at once, while rarely read state gets a cell of its own
+    /// and is only loaded by the messages that need it.
+    #[ink(storage)]
+    #[cfg_attr(test, derive(Default))]
+    pub struct CrossContractFlipper {
+        root: FlipperRoot,
+        delegate_to: Lazy<Hash, ManualKey<{ delegator_key(1) }>>,
+        owner: Lazy<AccountId, ManualKey<{ delegator_key(0) }>>,
+        xcm_config: Lazy<XcmConfig, ManualKey<{ delegator_key(15) }>>,
+        nonces: Mapping<AccountId, u64, ManualKey<{ delegator_key(16) }>>,
+        fee_pool: Lazy<Balance, ManualKey<{ delegator_key(17) }>>,
+        session_keys: Mapping<AccountId, SessionKey, ManualKey<{ delegator_key(18) }>>,
+        commitments: Mapping<AccountId, Commitment, ManualKey<{ delegator_key(19) }>>,
+        oracle: Lazy<AccountId, ManualKey<{ delegator_key(20) }>>,
+        /// Pending scheduled flips, ordered by their block.
+        scheduled_flips: Lazy<Vec<ScheduledFlip>, ManualKey<{ delegator_key(21) }>>,
+        next_schedule_id: Lazy<u64, ManualKey<{ delegator_key(22) }>>,
+        last_flip_at: Mapping<AccountId, BlockNumber, ManualKey<{ delegator_key(23) }>>,
+        /// The block the counter belongs to and the number of flips in it.
+        block_flips: Lazy<(BlockNumber, u32), ManualKey<{ delegator_key(24) }>>,
+        /// Price and length in blocks of one subscription period.
+        subscription_terms: Lazy<(Balance, BlockNumber), ManualKey<{ delegator_key(25) }>>,
+        subscriptions: Mapping<AccountId, BlockNumber, ManualKey<{ delegator_key(26) }>>,
+        auction: Lazy<Auction, ManualKey<{ delegator_key(27) }>>,
+        /// Escrowed bids per auction id and bidder.
+        bids: Mapping<(u32, AccountId), Balance, ManualKey<{ delegator_key(28) }>>,
+        /// Sum of all escrowed bids.
+        bids_total: Lazy<Balance, ManualKey<{ delegator_key(29) }>>,
+        allowlist_root: Lazy<Hash, ManualKey<{ delegator_key(30) }>>,
+        members: Lazy<Vec<AccountId>, ManualKey<{ delegator_key(31) }>>,
+        proposals: Mapping<u32, Proposal, ManualKey<{ delegator_key(32) }>>,
+        next_proposal_id: Lazy<u32, ManualKey<{ delegator_key(33) }>>,
+        /// Whether a member voted on a proposal.
+        votes: Mapping<(u32, AccountId), (), ManualKey<{ delegator_key(34) }>>,
+        /// Voting weight of each member, snapshotted per proposal.
+        vote_weights: Mapping<(u32, AccountId), u128, ManualKey<{ delegator_key(35) }>>,
+        governance_token: Lazy<AccountId, ManualKey<{ delegator_key(36) }>>,
+        governance_config: Lazy<GovernanceConfig, ManualKey<{ delegator_key(37) }>>,
+        /// Instance of the other contract the query messages read from.
+        other_contract: Lazy<AccountId, ManualKey<{ delegator_key(38) }>>,
+        /// Number of flips executed through the delegate.
+        flip_count: Lazy<Counter, ManualKey<{ delegator_key(9) }>>,
+        /// Rolling average of the gas the delegate's `flip` used.
+        delegate_gas: Lazy<u64, ManualKey<{ delegator_key(11) }>>,
+        /// Outcomes of outbound cross-contract calls per selector.
+        call_stats: Mapping<[u8; 4], CallStats, ManualKey<{ delegator_key(10) }>>,
+        /// Audited logic code hashes the contract may delegate to.
+        approved_code_hashes: Mapping<Hash, (), ManualKey<{ delegator_key(2) }>>,
+        /// Number of transfers received through `receive`.
+        received_count: Lazy<Counter, ManualKey<{ delegator_key(39) }>>,
+        /// Selectors of the registry, by method name.
+        selectors: Mapping<String, [u8; 4], ManualKey<{ delegator_key(40) }>>,
+        /// Names in the selector registry, in registration order.
+        selector_names: Lazy<Vec<String>, ManualKey<{ delegator_key(41) }>>,
+        /// Number of flips attributed to each account.
+        flips_by: Mapping<AccountId, u64, ManualKey<{ delegator_key(42) }>>,
+        attributed_flips: Lazy<Counter, ManualKey<{ delegator_key(43) }>>,
+        flippers: Lazy<Counter, ManualKey<{ delegator_key(44) }>>,
+        /// Accounts with the highest scores, highest first.
+        leaderboard: Lazy<Vec<LeaderboardEntry>, ManualKey<{ delegator_key(45) }>>,
+        current_streak: Lazy<Streak, ManualKey<{ delegator_key(46) }>>,
+        longest_streak: Lazy<Streak, ManualKey<{ delegator_key(47) }>>,
+        /// PSP34 contract minting the achievement badges.
+        badge_contract: Lazy<AccountId, ManualKey<{ delegator_key(48) }>>,
+        /// Flip counts that earn a badge, in increasing order.
+        badge_milestones: Lazy<Vec<u64>, ManualKey<{ delegator_key(49) }>>,
+        /// Badges minted per account and milestone.
+        badges: Mapping<(AccountId, u64), (), ManualKey<{ delegator_key(50) }>>,
+        /// Fee of a flip with a referrer and the referrer's share in percent.
+        referral_terms: Lazy<(Balance, u8), ManualKey<{ delegator_key(51) }>>,
+        /// Referral rewards each referrer can claim.
+        referral_rewards: Mapping<AccountId, Balance, ManualKey<{ delegator_key(52) }>>,
+        /// Sum of all unclaimed referral rewards.
+        referral_rewards_total: Lazy<Balance, ManualKey<{ delegator_key(53) }>>,
+        /// The latest `HISTORY_SIZE` attributed flips, by their position
+        /// modulo `HISTORY_SIZE`.
+        history: Mapping<u64, FlipRecord, ManualKey<{ delegator_key(54) }>>,
+        /// Number of flips ever added to the history.
+        history_len: Lazy<u64, ManualKey<{ delegator_key(55) }>>,
+        expiry: Lazy<Option<Expiry>, ManualKey<{ delegator_key(56) }>>,
+        /// Length of an epoch in blocks and the value it starts with.
+        epoch_config: Lazy<(BlockNumber, bool), ManualKey<{ delegator_key(57) }>>,
+        /// The epoch the value was last set in.
+        value_epoch: Lazy<BlockNumber, ManualKey<{ delegator_key(58) }>>,
+        epoch_stats: Mapping<BlockNumber, EpochStats, ManualKey<{ delegator_key(59) }>>,
+        /// Number of flips attributed to each account per epoch.
+        epoch_flips: Mapping<(BlockNumber, AccountId), u64, ManualKey<{ delegator_key(60) }>>,
+        /// Leaderboard score of each account and the decay period it was
+        /// last updated in.
+        scores: Mapping<AccountId, (u64, BlockNumber), ManualKey<{ delegator_key(61) }>>,
+        /// Decay period the leaderboard scores were last updated in.
+        leaderboard_index: Lazy<BlockNumber, ManualKey<{ delegator_key(62) }>>,
+        /// Confirmations a flip needs and the number of blocks to collect
+        /// them in.
+        confirmation_terms: Lazy<(u32, BlockNumber), ManualKey<{ delegator_key(63) }>>,
+        pending_confirmation: Lazy<Option<PendingConfirmation>, ManualKey<{ delegator_key(64) }>>,
+        next_confirmation_id: Lazy<u32, ManualKey<{ delegator_key(65) }>>,
+        confirmed_by: Mapping<(u32, AccountId), (), ManualKey<{ delegator_key(66) }>>,
+        guardian: Lazy<AccountId, ManualKey<{ delegator_key(67) }>>,
+        last_flip: Lazy<Option<LastFlip>, ManualKey<{ delegator_key(68) }>>,
+        frozen: Mapping<AccountId, (), ManualKey<{ delegator_key(69) }>>,
+        /// Per-account values, next to the global `value`.
+        account_values: Mapping<AccountId, bool, ManualKey<{ delegator_key(70) }>>,
+        /// `flip_count` as of the latest import, or zero.
+        imported_flip_count: Lazy<Counter, ManualKey<{ delegator_key(71) }>>,
+        /// The previous instance `migrate_from` pulled the state from.
+        migrated_from: Lazy<AccountId, ManualKey<{ delegator_key(72) }>>,
+        /// Tunables, see `Config`.
+        config: Lazy<Config, ManualKey<{ delegator_key(8) }>>,
+        /// `layout_hash` the delegate reported when it was set, if it was
+        /// set through `set_delegate_to` or governance.
+        delegate_layout: Lazy<Hash, ManualKey<{ delegator_key(3) }>>,
+        /// Set while `multicall` or `call_named` calls out of the contract.
+        entered: Lazy<bool, ManualKey<{ delegator_key(13) }>>,
+        /// Set for good by `disable_delegation_forever`.
+        delegation_disabled: Lazy<bool, ManualKey<{ delegator_key(4) }>>,
+        upgrade_signers: Lazy<Option<UpgradeSigners>, ManualKey<{ delegator_key(5) }>>,
+        /// Number of delegate changes the upgrade signers authorized.
+        upgrade_nonce: Lazy<u64, ManualKey<{ delegator_key(6) }>>,
+        key_rotations: Lazy<u64, ManualKey<{ delegator_key(7) }>>,
+        /// Proposals of each rotated out key with a lower id are revoked.
+        revoked_proposals: Mapping<AccountId, u32, ManualKey<{ delegator_key(73) }>>,
+        /// `seq` of the next `AuditLog` event.
+        audit_seq: Lazy<u64, ManualKey<{ delegator_key(12) }>>,
+        /// Idempotency keys of the latest signed flips, by their signer.
+        idempotency_keys: Mapping<IdempotencyKey, (), ManualKey<{ delegator_key(74) }>>,
+        /// The same keys by their position modulo `IDEMPOTENCY_KEYS`, so the
+        /// oldest one can be forgotten.
+        idempotency_ring: Mapping<u32, IdempotencyKey, ManualKey<{ delegator_key(75) }>>,
+        /// Number of idempotency keys ever remembered.
+        idempotency_len: Lazy<u64, ManualKey<{ delegator_key(76) }>>,
+        /// Code hashes locked as delegate dependencies, in locking order.
+        locked_code_hashes: Lazy<Vec<Hash>, ManualKey<{ delegator_key(14) }>>,
+    }
+
+    impl CrossContractFlipper {
+        /// Creates a new delegator smart contract with an initial value,
+        /// and the code hash of the contract it will delegate to
+        ///
+        /// Additionally, this code hash will be locked to prevent its deletion
+        /// because it is a dependency of this contract.
+        #[ink(constructor)]
+        pub fn new(init_value: bool, code_hash: Hash) -> Self {
+            let mut contract = Self::empty();
+            contract.setup(InitConfig {
+                init_value,
+                code_hash: Some(code_hash),
+                ..InitConfig::default()
+            });
+            contract
+        }
+
+        /// Creates a delegator that doesn't delegate anywhere yet, for when the
+        /// logic contract isn't known at deploy time.
+        ///
+        /// The delegate messages fail with `DelegateNotSet` until the owner
+        /// approves a code hash and sets it with `set_delegate_to`.
+        #[ink(constructor)]
+        pub fn new_default() -> Self {
+            let mut contract = Self::empty();
+            contract.setup(InitConfig::default());
+            contract
+        }
+
+        /// Creates a delegator as described by `init`, so deployment tooling
+        /// only has to fill in what differs from the defaults.
+        #[ink(constructor)]
+        pub fn with_config(init: InitConfig) -> Result<Self> {
+            let mut contract = Self::empty();
+            contract.ensure_payable(init.relayer_fee)?;
+            contract.setup(init);
+            Ok(contract)
+        }
+
+        /// Storage with every field empty, for the constructors to fill in.
+        fn empty() -> Self {
+            Self {
+                root: FlipperRoot::default(),
+                delegate_to: Lazy::new(),
+                owner: Lazy::new(),
+                approved_code_hashes: Mapping::new(),
+                xcm_config: Lazy::new(),
+                nonces: Mapping::new(),
+                fee_pool: Lazy::new(),
+                session_keys: Mapping::new(),
+                commitments: Mapping::new(),
+                oracle: Lazy::new(),
+                scheduled_flips: Lazy::new(),
+                next_schedule_id: Lazy::new(),
+                last_flip_at: Mapping::new(),
+                block_flips: Lazy::new(),
+                subscription_terms: Lazy::new(),
+                subscriptions: Mapping::new(),
+                auction: Lazy::new(),
+                bids: Mapping::new(),
+                bids_total: Lazy::new(),
+                allowlist_root: Lazy::new(),
+                members: Lazy::new(),
+                proposals: Mapping::new(),
+                next_proposal_id: Lazy::new(),
+                votes: Mapping::new(),
+                vote_weights: Mapping::new(),
+                governance_token: Lazy::new(),
+                governance_config: Lazy::new(),
+                other_contract: Lazy::new(),
+                flip_count: Lazy::new(),
+                delegate_gas: Lazy::new(),
+                call_stats: Mapping::new(),
+                received_count: Lazy::new(),
+                selectors: Mapping::new(),
+                selector_names: Lazy::new(),
+                flips_by: Mapping::new(),
+                attributed_flips: Lazy::new(),
+                flippers: Lazy::new(),
+                leaderboard: Lazy::new(),
+                current_streak: Lazy::new(),
+                longest_streak: Lazy::new(),
+                badge_contract: Lazy::new(),
+                badge_milestones: Lazy::new(),
+                badges: Mapping::new(),
+                referral_terms: Lazy::new(),
+                referral_rewards: Mapping::new(),
+                referral_rewards_total: Lazy::new(),
+                history: Mapping::new(),
+                history_len: Lazy::new(),
+                expiry: Lazy::new(),
+                epoch_config: Lazy::new(),
+                value_epoch: Lazy::new(),
+                epoch_stats: Mapping::new(),
+                epoch_flips: Mapping::new(),
+                scores: Mapping::new(),
+                leaderboard_index: Lazy::new(),
+                confirmation_terms: Lazy::new(),
+                pending_confirmation: Lazy::new(),
+                next_confirmation_id: Lazy::new(),
+                confirmed_by: Mapping::new(),
+                guardian: Lazy::new(),
+                last_flip: Lazy::new(),
+                frozen: Mapping::new(),
+                account_values: Mapping::new(),
+                imported_flip_count: Lazy::new(),
+                migrated_from: Lazy::new(),
+                config: Lazy::new(),
+                delegate_layout: Lazy::new(),
+                entered: Lazy::new(),
+                delegation_disabled: Lazy::new(),
+                upgrade_signers: Lazy::new(),
+                upgrade_nonce: Lazy::new(),
+                key_rotations: Lazy::new(),
+                revoked_proposals: Mapping::new(),
+                audit_seq: Lazy::new(),
+                idempotency_keys: Mapping::new(),
+                idempotency_ring: Mapping::new(),
+                idempotency_len: Lazy::new(),
+                locked_code_hashes: Lazy::new(),
+            }
+        }
+
+        /// Sets up an instance no constructor ran for, e.g. behind a minimal
+        /// proxy, like `with_config` does.
+        ///
+        /// Callable once, and not at all on instances created by a
+        /// constructor, so nobody can take over an instance by initializing
+        /// it again. The proxy has to have written the root cell, a single
+        /// `bool`, since ink! doesn't dispatch messages without it. Set up the
+        /// proxy and call this in the same transaction, or someone else may
+        /// call it first.
+        #[ink(message)]
+        pub fn initialize(&mut self, init: InitConfig) -> Result<()> {
+            if self.owner.get().is_some() {
+                return Err(Error::AlreadyInitialized);
+            }
+            self.ensure_payable(init.relayer_fee)?;
+            self.setup(init);
+            Ok(())
+        }
+
+        /// Applies `init` to empty storage; the fee has to be validated.
+        fn setup(&mut self, init: InitConfig) {
+            if let Some(code_hash) = init.code_hash {
+                self.lock_dependency(code_hash);
+                self.approved_code_hashes.insert(code_hash, &());
+                self.delegate_to.set(&code_hash);
+            }
+            self.owner
+                .set(&init.owner.unwrap_or_else(|| self.env().caller()));
+            self.root.value = init.init_value;
+            if init.relayer_fee != 0 {
+                self.config.set(&Config {
+                    relayer_fee: init.relayer_fee,
+                    ..Config::default()
+                });
+            }
+        }
+
+        /// Calls the delegate's `flip` through a delegate call and returns
+        /// what ran.
+        ///
+        /// The delegate's result is decoded, so this is a normal call rather
+        /// than a tail call; `delegate_flip_with(DelegateMode::TailCall)`
+        /// makes the cheaper tail call instead. The flip is attributed to the
+        /// caller, so it has to pass the same checks as any other flip.
+        #[ink(message)]
+        pub fn call_delegate_flip(&mut self) -> Result<FlipOutcome> {
+            let caller = self.env().caller();
+            self.admit_flips(caller, 1)?;
+            let target = self.delegate()?;
+            let gas_used = self.delegate_flip()?;
+            self.commit_flips(caller, 1);
+            self.record_flip_by(caller);
+            self.env().emit_event(Flipped {
+                by: caller,
+                value: self.root.value,
+                memo: None,
+            });
+            Ok(FlipOutcome {
+                target,
+                flags: CallFlags::empty().bits(),
+                value: self.root.value,
+                gas_used,
+            })
+        }
+
+        /// Flips through the delegate with `TAIL_CALL`, which hands the
+        /// delegate's output straight to the caller.
+        ///
+        /// Only returns if the call couldn't be made, with the error that
+        /// reverts the attempt.
+        fn tail_call_flip(&mut self) -> Result<Option<FlipOutcome>> {
+            let caller = self.env().caller();
+            self.admit_flips(caller, 1)?;
+            let delegate = self.delegate()?;
+            self.settle_value();
+            // Nothing runs after a successful tail call, so count beforehand.
+            // A failed one reverts all of it.
+            self.commit_flips(caller, 1);
+            self.count_flip();
+            self.arm_expiry(self.root.value, !self.root.value, self.flip_ttl());
+            self.open_veto_window(self.root.value);
+            let selector = ink::selector_bytes!("flip");
+            self.record_call(selector, true);
+            trace!("tail_call_flip: delegate={:?}", delegate);
+            let _result = build_call::<Environment>()
+                .delegate(delegate)
+                .call_flags(CallFlags::TAIL_CALL)
+                .exec_input(ExecutionInput::new(Selector::new(selector)))
+                .returns::<()>()
+                .try_invoke();
+            trace!("tail_call_flip: failed {:?}", _result);
+            Err(Error::DelegateCallFailed)
+        }
+
+        /// Returns the parameters of the delegate call `call_delegate_flip`
+        /// makes, so off-chain tools can dry-run exactly that call
+        #[ink(message)]
+        pub fn delegate_call_params(&self) -> Result<DelegateCallParams> {
+            let selector = ink::selector_bytes!("flip");
+            Ok(DelegateCallParams {
+                code_hash: self.delegate()?,
+                selector,
+                input: ExecutionInput::new(Selector::new(selector)).encode(),
+                flags: CallFlags::empty().bits(),
+                limits: None,
+            })
+        }
+
+        /// Flips through the delegate in either `mode`.
+        ///
+        /// A decoded call works like `call_delegate_flip`: it decodes the
+        /// delegate's result, measures its gas, emits `Flipped` and returns
+        /// `Some` outcome. A tail call is cheaper, but hands the delegate's
+        /// output straight to the caller, so nothing runs afterwards and the
+        /// caller gets whatever the delegate's `flip` returns, `()` in the
+        /// case of `other-contract`, instead of a `Result<Option<_>>`.
+        ///
+        /// In both modes a failure reverts the flip with the `Error`.
+        #[ink(message)]
+        pub fn delegate_flip_with(&mut self, mode: DelegateMode) -> Result<Option<FlipOutcome>> {
+            match mode {
+                DelegateMode::Decoded => self.call_delegate_flip().map(Some),
+                DelegateMode::TailCall => self.tail_call_flip(),
+            }
+        }
+
+        /// Flips only if the random byte fetched through the `FetchRandom`
+        /// chain extension is odd.
+        ///
+        /// Returns whether the value was flipped.
+        #[ink(message)]
+        pub fn random_flip(&mut self) -> Result<bool> {
+            let subject = *self.env().caller().as_ref();
+            let random = self.env().extension().fetch_random(subject)?;
+            if random[0] % 2 == 0 {
+                return Ok(false);
+            }
+            self.flip_for(self.env().caller())?;
+            Ok(true)
+        }
+
+        /// Returns the fee paid to whoever relays a signed flip
+        #[ink(message)]
+        pub fn relayer_fee(&self) -> Balance {
+            self.get_config().relayer_fee
+        }
+
+        /// Sets the fee paid to relayers of signed flips, zero disables it.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_relayer_fee(&mut self, fee: Balance) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_relayer_fee"))?;
+            self.update_config(|config| config.relayer_fee = fee)
+        }
+
+        /// Returns the balance set aside for paying relayers
+        #[ink(message)]
+        pub fn fee_pool(&self) -> Balance {
+            self.fee_pool.get().unwrap_or_default()
+        }
+
+        /// Adds the transferred value to the fee pool.
+        #[ink(message, payable)]
+        pub fn fund_fee_pool(&mut self) -> Result<()> {
+            self.credit_fee_pool(self.env().transferred_value())
+        }
+    }
+
+    /// Meta-transactions: flips signed off-chain and submitted by a relayer,
+    /// and session keys flipping on an account's behalf.
+    ///
+    /// Only part of builds with the `meta-tx` feature. The relayer fee
+    /// stays part of `Config` either way.
+    impl CrossContractFlipper {
+        /// Flips on behalf of whoever signed `payload` with their ECDSA key.
+        ///
+        /// The signer is the account derived from the recovered public key,
+        /// so a relayer can submit (and pay for) the transaction for them.
+        /// `signature` is a recoverable secp256k1 signature over the
+        /// BLAKE2-256 hash of the SCALE encoded `payload`.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn flip_with_signature(
+            &mut self,
+            payload: FlipPayload,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            self.ensure_message_enabled(MessageFlag::MetaTx)?;
+            if payload.contract != self.env().account_id() {
+                return Err(Error::WrongContract);
+            }
+            let message_hash = self.env().hash_encoded::<Blake2x256, _>(&payload);
+            let public_key = self
+                .env()
+                .ecdsa_recover(&signature, &message_hash)
+                .map_err(|_| Error::InvalidSignature)?;
+
+            // Substrate derives ECDSA accounts from the hash of the compressed key.
+            let signer = AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key));
+            self.execute_signed_flip(signer, &payload)
+        }
+
+        /// Flips on behalf of `signer`, who signed `payload` with their
+        /// sr25519 key.
+        ///
+        /// For sr25519 accounts the account id is the public key itself, so
+        /// the signature is checked against `signer` directly. `signature` is
+        /// over the SCALE encoded `payload`.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn flip_with_sr25519(
+            &mut self,
+            payload: FlipPayload,
+            signer: AccountId,
+            signature: [u8; 64],
+        ) -> Result<()> {
+            self.ensure_message_enabled(MessageFlag::MetaTx)?;
+            if payload.contract != self.env().account_id() {
+                return Err(Error::WrongContract);
+            }
+            self.env()
+                .sr25519_verify(&signature, &payload.encode(), signer.as_ref())
+                .map_err(|_| Error::InvalidSignature)?;
+
+            self.execute_signed_flip(signer, &payload)
+        }
+
+        /// Returns the nonce the next signed flip of `account` has to carry
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn nonce_of(&self, account: AccountId) -> u64 {
+            self.nonces.get(account).unwrap_or_default()
+        }
+
+        /// Lets `key` flip on the caller's behalf until block `expires_at`.
+        ///
+        /// Replaces any session key the caller registered before.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn register_session_key(
+            &mut self,
+            key: AccountId,
+            expires_at: BlockNumber,
+        ) -> Result<()> {
+            if expires_at <= self.env().block_number() {
+                return Err(Error::InvalidExpiry);
+            }
+            let account = self.env().caller();
+            self.ensure_not_frozen(account)?;
+            self.session_keys
+                .insert(account, &SessionKey { key, expires_at });
+            self.env().emit_event(SessionKeyRegistered {
+                account,
+                key,
+                expires_at,
+            });
+            Ok(())
+        }
+
+        /// Revokes the caller's session key, if any.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn revoke_session_key(&mut self) {
+            let account = self.env().caller();
+            if self.session_keys.take(account).is_some() {
+                self.env().emit_event(SessionKeyRevoked { account });
+            }
+        }
+
+        /// Returns the session key registered by `account`, if any
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn session_key_of(&self, account: AccountId) -> Option<SessionKey> {
+            self.session_keys.get(account)
+        }
+
+        /// Flips on behalf of `account`, using the caller as its session key.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn flip_as(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_message_enabled(MessageFlag::MetaTx)?;
+            let session = self
+                .session_keys
+                .get(account)
+                .filter(|session| session.key == self.env().caller())
+                .ok_or(Error::NotSessionKey)?;
+            if self.env().block_number() >= session.expires_at {
+                return Err(Error::SessionExpired);
+            }
+            self.flip_for(account)
+        }
+
+        /// Returns whether `signer` used `key` in one of the latest
+        /// `IDEMPOTENCY_KEYS` signed flips with a key, counted across all
+        /// signers
+        ///
+        /// The window is global, so the more other signers use keys, the
+        /// sooner a key of `signer` is forgotten.
+        #[cfg(feature = "meta-tx")]
+        #[ink(message)]
+        pub fn is_idempotency_key_used(&self, signer: AccountId, key: [u8; 32]) -> bool {
+            self.idempotency_keys.contains((signer, key))
+        }
+
+        /// Remembers `key` of `signer`, forgetting the oldest key once
+        /// `IDEMPOTENCY_KEYS` are remembered.
+        #[cfg(feature = "meta-tx")]
+        fn remember_idempotency_key(&mut self, signer: AccountId, key: [u8; 32]) {
+            let len = self.idempotency_len.get().unwrap_or_default();
+            let slot = (len % u64::from(IDEMPOTENCY_KEYS)) as u32;
+            if let Some(oldest) = self.idempotency_ring.get(slot) {
+                self.idempotency_keys.remove(oldest);
+            }
+            self.idempotency_ring.insert(slot, &(signer, key));
+            self.idempotency_keys.insert((signer, key), &());
+            self.idempotency_len.set(&len.saturating_add(1));
+        }
+
+        /// Consumes the signer's nonce, flips on their behalf and pays the
+        /// relayer, if a relayer fee is configured.
+        ///
+        /// Does nothing if the payload's idempotency key was used already,
+        /// so a retried submission succeeds without flipping again.
+        #[cfg(feature = "meta-tx")]
+        fn execute_signed_flip(&mut self, signer: AccountId, payload: &FlipPayload) -> Result<()> {
+            if let Some(key) = payload.idempotency_key {
+                if self.is_idempotency_key_used(signer, key) {
+                    return Ok(());
+                }
+            }
+            let nonce = payload.nonce;
+            if nonce != self.nonce_of(signer) {
+                return Err(Error::InvalidNonce);
+            }
+            if let Some(key) = payload.idempotency_key {
+                self.remember_idempotency_key(signer, key);
+            }
+            self.nonces
+                .insert(signer, &nonce.checked_add(1).ok_or(Error::Overflow)?);
+            self.flip_for(signer)?;
+
+            let fee = self.relayer_fee();
+            if fee == 0 {
+                return Ok(());
+            }
+            let relayer = self.env().caller();
+            self.pay_from_fee_pool(relayer, fee)?;
+            self.env().emit_event(RelayerPaid {
+                relayer,
+                signer,
+                fee,
+            });
+            Ok(())
+        }
+    }
+
+    /// Delegate changes authorized by signatures the upgrade signers handed
+    /// out beforehand, as an alternative to voting on a governance proposal.
+    impl CrossContractFlipper {
+        /// Returns the accounts that can authorize a delegate change with
+        /// `set_delegate_to_signed`, if any
+        #[ink(message)]
+        pub fn upgrade_signers(&self) -> Option<UpgradeSigners> {
+            self.upgrade_signers.get().flatten()
+        }
+
+        /// Replaces the upgrade signers, `None` removes them.
+        ///
+        /// There can be at most `MAX_UPGRADE_SIGNERS`, each listed once, and
+        /// the threshold has to be between 1 and their number. Only callable
+        /// by the owner.
+        #[ink(message)]
+        pub fn set_upgrade_signers(&mut self, signers: Option<UpgradeSigners>) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_upgrade_signers"))?;
+            if let Some(signers) = &signers {
+                signers.validate()?;
+            }
+            self.upgrade_signers.set(&signers);
+            Ok(())
+        }
+
+        /// Returns the nonce the next `UpgradeAction` has to carry
+        #[ink(message)]
+        pub fn upgrade_nonce(&self) -> u64 {
+            self.upgrade_nonce.get().unwrap_or_default()
+        }
+
+        /// Returns the BLAKE2-256 hash of the SCALE encoded `UpgradeAction`
+        /// making `code_hash` the delegate, which the upgrade signers sign
+        #[ink(message)]
+        pub fn upgrade_action_hash(&self, code_hash: Hash) -> Hash {
+            let action = UpgradeAction {
+                contract: self.env().account_id(),
+                code_hash,
+                nonce: self.upgrade_nonce(),
+            };
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&action))
+        }
+
+        /// Delegates to `code_hash` from now on, authorized by the
+        /// `signatures` of at least the threshold of upgrade signers over its
+        /// `upgrade_action_hash`.
+        ///
+        /// Anyone can submit the signatures, and each set of them works only
+        /// once. The same rules as for `set_delegate_to` apply to the code
+        /// hash.
+        #[ink(message)]
+        pub fn set_delegate_to_signed(
+            &mut self,
+            code_hash: Hash,
+            signatures: Vec<UpgradeSignature>,
+        ) -> Result<()> {
+            let upgrade_signers = self.upgrade_signers().ok_or(Error::UpgradeSignersNotSet)?;
+            let action_hash = self.upgrade_action_hash(code_hash);
+            let mut signed = Vec::with_capacity(signatures.len());
+            for signature in &signatures {
+                let signer = self.upgrade_signer(signature, &action_hash)?;
+                if !upgrade_signers.signers.contains(&signer) {
+                    return Err(Error::NotUpgradeSigner);
+                }
+                if signed.contains(&signer) {
+                    return Err(Error::DuplicateSignature);
+                }
+                signed.push(signer);
+            }
+            if signed.len() < upgrade_signers.threshold as usize {
+                return Err(Error::ThresholdNotMet);
+            }
+            self.log_admin(ink::selector_bytes!("set_delegate_to_signed"));
+            self.update_delegate_to(code_hash)?;
+            let nonce = self.upgrade_nonce();
+            self.upgrade_nonce
+                .set(&nonce.checked_add(1).ok_or(Error::Overflow)?);
+            Ok(())
+        }
+
+        /// Returns the account that made `signature` over `action_hash`.
+        fn upgrade_signer(
+            &self,
+            signature: &UpgradeSignature,
+            action_hash: &Hash,
+        ) -> Result<AccountId> {
+            match signature {
+                UpgradeSignature::Sr25519 { signer, signature } => {
+                    self.env()
+                        .sr25519_verify(signature, action_hash.as_ref(), signer.as_ref())
+                        .map_err(|_| Error::InvalidSignature)?;
+                    Ok(*signer)
+                }
+                UpgradeSignature::Ecdsa(signature) => {
+                    let public_key = self
+                        .env()
+                        .ecdsa_recover(signature, &<[u8; 32]>::from(*action_hash))
+                        .map_err(|_| Error::InvalidSignature)?;
+                    // Substrate derives ECDSA accounts from the hash of the
+                    // compressed key.
+                    Ok(AccountId::from(
+                        self.env().hash_bytes::<Blake2x256>(&public_key),
+                    ))
+                }
+            }
+        }
+    }
+
+    impl CrossContractFlipper {
+        /// Commits to a flip direction without disclosing it.
+        ///
+        /// `hash` is the BLAKE2-256 hash of the SCALE encoded `(value, salt)`
+        /// later passed to `reveal`. An expired commitment is replaced, an
+        /// open one has to be revealed first.
+        #[ink(message)]
+        pub fn commit(&mut self, hash: Hash) -> Result<()> {
+            let caller = self.env().caller();
+            self.ensure_not_frozen(caller)?;
+            let now = self.env().block_number();
+            if let Some(commitment) = self.commitments.get(caller) {
+                if now < commitment.expires_at {
+                    return Err(Error::CommitmentPending);
+                }
+            }
+            let expires_at = now.saturating_add(self.reveal_window());
+            self.commitments
+                .insert(caller, &Commitment { hash, expires_at });
+            Ok(())
+        }
+
+        /// Reveals the caller's commitment and sets the value to the
+        /// committed one, flipping if it differs.
+        ///
+        /// Returns whether the value was flipped.
+        #[ink(message)]
+        pub fn reveal(&mut self, value: bool, salt: [u8; 32]) -> Result<bool> {
+            let caller = self.env().caller();
+            let commitment = self.commitments.get(caller).ok_or(Error::NoCommitment)?;
+            if self.env().block_number() >= commitment.expires_at {
+                return Err(Error::CommitmentExpired);
+            }
+            let hash = self.env().hash_encoded::<Blake2x256, _>(&(value, salt));
+            if Hash::from(hash) != commitment.hash {
+                return Err(Error::RevealMismatch);
+            }
+            self.commitments.remove(caller);
+
+            self.settle_value();
+            if self.root.value == value {
+                return Ok(false);
+            }
+            self.flip_for(caller)?;
+            Ok(true)
+        }
+
+        /// Returns the unrevealed commitment of `account`, if any
+        #[ink(message)]
+        pub fn commitment_of(&self, account: AccountId) -> Option<Commitment> {
+            self.commitments.get(account)
+        }
+
+        /// Returns the number of blocks a commitment can be revealed in
+        #[ink(message)]
+        pub fn reveal_window(&self) -> BlockNumber {
+            self.get_config().reveal_window
+        }
+
+        /// Sets the number of blocks a commitment can be revealed in.
+        ///
+        /// Only affects commitments made afterwards. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_reveal_window(&mut self, window: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_reveal_window"))?;
+            self.update_config(|config| config.reveal_window = window)
+        }
+
+        /// Returns the price oracle used by `flip_if_price_above`, if any
+        #[ink(message)]
+        pub fn oracle(&self) -> Option<AccountId> {
+            self.oracle.get()
+        }
+
+        /// Sets the price oracle used by `flip_if_price_above`.
+        ///
+        /// The oracle has to expose a `price` message returning a `u128`.
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_oracle(&mut self, oracle: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_oracle"))?;
+            self.ensure_contract(oracle, None)?;
+            self.oracle.set(&oracle);
+            Ok(())
+        }
+
+        /// Flips only if the oracle's current price is above `threshold`.
+        ///
+        /// Returns whether the value was flipped.
+        #[ink(message)]
+        pub fn flip_if_price_above(&mut self, threshold: u128) -> Result<bool> {
+            let oracle = self.oracle.get().ok_or(Error::OracleNotSet)?;
+            let selector = ink::selector_bytes!("price");
+            let result = build_call::<Environment>()
+                .call(oracle)
+                .exec_input(ExecutionInput::new(Selector::new(selector)))
+                .returns::<u128>()
+                .try_invoke();
+            self.record_call(selector, matches!(result, Ok(Ok(_))));
+            trace!(
+                "flip_if_price_above: oracle={:?} threshold={} -> {:?}",
+                oracle,
+                threshold,
+                result
+            );
+            let price = result
+                .map_err(|_| Error::OracleCallFailed)?
+                .map_err(|_| Error::OracleCallFailed)?;
+            if price <= threshold {
+                return Ok(false);
+            }
+            self.flip_for(self.env().caller())?;
+            Ok(true)
+        }
+
+        /// Schedules a flip on the caller's behalf, to be executed by a
+        /// keeper calling `poke` from block `at` on.
+        ///
+        /// The transferred value, at least the `keeper_bounty`, is held as the
+        /// bounty of the keeper executing the flip. An account can have at
+        /// most `MAX_SCHEDULED_FLIPS_PER_ACCOUNT` flips pending. Returns the
+        /// id of the scheduled flip.
+        #[ink(message, payable)]
+        pub fn schedule_flip(&mut self, at: BlockNumber) -> Result<u64> {
+            if at <= self.env().block_number() {
+                return Err(Error::NotInFuture);
+            }
+            let deposit = self.env().transferred_value();
+            if deposit < self.keeper_bounty() {
+                return Err(Error::InsufficientPayment);
+            }
+            self.ensure_payable(deposit)?;
+            let by = self.env().caller();
+            let mut scheduled = self.scheduled_flips();
+            if scheduled.len() >= MAX_SCHEDULED_FLIPS {
+                return Err(Error::ScheduleFull);
+            }
+            if scheduled.iter().filter(|flip| flip.by == by).count()
+                >= MAX_SCHEDULED_FLIPS_PER_ACCOUNT
+            {
+                return Err(Error::TooManyScheduledFlips);
+            }
+            let id = self.next_schedule_id.get().unwrap_or_default();
+            self.next_schedule_id
+                .set(&id.checked_add(1).ok_or(Error::Overflow)?);
+
+            let position = scheduled.partition_point(|flip| flip.at <= at);
+            scheduled.insert(
+                position,
+                ScheduledFlip {
+                    id,
+                    by,
+                    at,
+                    deposit,
+                },
+            );
+            self.scheduled_flips.set(&scheduled);
+            self.env().emit_event(FlipScheduled { id, by, at });
+            Ok(id)
+        }
+
+        /// Returns the pending scheduled flips, ordered by their block
+        #[ink(message)]
+        pub fn scheduled_flips(&self) -> Vec<ScheduledFlip> {
+            self.scheduled_flips.get().unwrap_or_default()
+        }
+
+        /// Executes every scheduled flip that is due and pays the caller the
+        /// deposits of the executed ones.
+        ///
+        /// A due flip that fails doesn't stop the others: it is dropped,
+        /// emits `ScheduledFlipFailed` and its deposit goes back to the
+        /// account that scheduled it. Returns the number of executed flips.
+        #[ink(message)]
+        pub fn poke(&mut self) -> Result<u32> {
+            let now = self.env().block_number();
+            let mut scheduled = self.scheduled_flips();
+            let due = scheduled.partition_point(|flip| flip.at <= now);
+            if due == 0 {
+                return Err(Error::NothingDue);
+            }
+            let remaining = scheduled.split_off(due);
+            self.scheduled_flips.set(&remaining);
+            let mut executed = 0u32;
+            let mut bounty: Balance = 0;
+            for flip in &scheduled {
+                match self.flip_for(flip.by) {
+                    Ok(()) => {
+                        executed += 1;
+                        bounty = bounty.saturating_add(flip.deposit);
+                    }
+                    Err(error) => {
+                        self.env().emit_event(ScheduledFlipFailed {
+                            id: flip.id,
+                            by: flip.by,
+                            code: error.code(),
+                        });
+                        self.refund_deposit(flip)?;
+                    }
+                }
+            }
+
+            // Every deposit is zero or at least the existential deposit, so
+            // is their sum.
+            if bounty > 0 {
+                let keeper = self.env().caller();
+                self.ensure_balance(bounty)?;
+                self.env()
+                    .transfer(keeper, bounty)
+                    .map_err(|_| Error::TransferFailed)?;
+                self.env().emit_event(KeeperPaid {
+                    keeper,
+                    executed,
+                    bounty,
+                });
+            }
+            Ok(executed)
+        }
+
+        /// Returns the minimum deposit of a scheduled flip
+        #[ink(message)]
+        pub fn keeper_bounty(&self) -> Balance {
+            self.get_config().keeper_bounty
+        }
+
+        /// Sets the minimum deposit of a scheduled flip.
+        ///
+        /// Flips already scheduled keep their deposit. Only callable by the
+        /// owner.
+        #[ink(message)]
+        pub fn set_keeper_bounty(&mut self, bounty: Balance) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_keeper_bounty"))?;
+            self.update_config(|config| config.keeper_bounty = bounty)
+        }
+
+        /// Pays the deposit of a dropped scheduled flip back to the account
+        /// that scheduled it, or into the fee pool if that account is gone.
+        fn refund_deposit(&mut self, flip: &ScheduledFlip) -> Result<()> {
+            if flip.deposit == 0 {
+                return Ok(());
+            }
+            self.ensure_balance(flip.deposit)?;
+            if self.env().transfer(flip.by, flip.deposit).is_err() {
+                self.credit_fee_pool(flip.deposit)?;
+            }
+            Ok(())
+        }
+
+        /// Adds `amount`, which the contract has just been paid, to the fee
+        /// pool.
+        fn credit_fee_pool(&mut self, amount: Balance) -> Result<()> {
+            let pool = self.fee_pool().checked_add(amount).ok_or(Error::Overflow)?;
+            self.fee_pool.set(&pool);
+            Ok(())
+        }
+
+        /// Transfers `amount` out of the fee pool to `to`.
+        #[cfg(feature = "meta-tx")]
+        fn pay_from_fee_pool(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            let pool = self.fee_pool();
+            if pool < amount {
+                return Err(Error::InsufficientFeePool);
+            }
+            self.ensure_transferable(amount)?;
+            self.fee_pool.set(&(pool - amount));
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Returns the number of blocks an account has to wait between flips
+        #[ink(message)]
+        pub fn cooldown(&self) -> BlockNumber {
+            self.get_config().cooldown
+        }
+
+        /// Sets the number of blocks an account has to wait between flips,
+        /// zero disables the cooldown.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_cooldown(&mut self, cooldown: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_cooldown"))?;
+            self.update_config(|config| config.cooldown = cooldown)
+        }
+
+        /// Returns the block of the last flip attributed to `account`, if any
+        #[ink(message)]
+        pub fn last_flip_at(&self, account: AccountId) -> Option<BlockNumber> {
+            self.last_flip_at.get(account)
+        }
+
+        /// Returns the maximum number of flips per block across all callers
+        #[ink(message)]
+        pub fn max_flips_per_block(&self) -> u32 {
+            self.get_config().max_flips_per_block
+        }
+
+        /// Sets the maximum number of flips per block across all callers,
+        /// zero disables the limit.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_max_flips_per_block(&mut self, max: u32) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_max_flips_per_block"))?;
+            self.update_config(|config| config.max_flips_per_block = max)
+        }
+
+        /// Returns the number of flips made in the current block
+        #[ink(message)]
+        pub fn flips_in_current_block(&self) -> u32 {
+            match self.block_flips.get() {
+                Some((block, count)) if block == self.env().block_number() => count,
+                _ => 0,
+            }
+        }
+
+        /// Returns the price and length in blocks of one subscription period
+        #[ink(message)]
+        pub fn subscription_terms(&self) -> (Balance, BlockNumber) {
+            self.subscription_terms.get().unwrap_or_default()
+        }
+
+        /// Sets the price and length in blocks of one subscription period.
+        ///
+        /// A non-zero price makes an active subscription a requirement for
+        /// flipping, zero disables subscriptions. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_subscription_terms(
+            &mut self,
+            price: Balance,
+            period: BlockNumber,
+        ) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_subscription_terms"))?;
+            if price > 0 && period == 0 {
+                return Err(Error::InvalidConfig);
+            }
+            self.subscription_terms.set(&(price, period));
+            Ok(())
+        }
+
+        /// Buys as many subscription periods as the transferred value covers.
+        ///
+        /// An active subscription is extended, an expired one starts anew at
+        /// the current block. The payment goes to the fee pool; whatever is
+        /// left over after the last whole period is not refunded.
+        #[ink(message, payable)]
+        pub fn subscribe(&mut self) -> Result<BlockNumber> {
+            let (price, period) = self.subscription_terms();
+            if price == 0 {
+                return Err(Error::SubscriptionsDisabled);
+            }
+            let paid = self.env().transferred_value();
+            let periods = BlockNumber::try_from(paid / price).unwrap_or(BlockNumber::MAX);
+            if periods == 0 {
+                return Err(Error::InsufficientPayment);
+            }
+
+            let account = self.env().caller();
+            let now = self.env().block_number();
+            let start = self.subscriptions.get(account).unwrap_or(now).max(now);
+            let expires_at = start.saturating_add(period.saturating_mul(periods));
+            self.credit_fee_pool(paid)?;
+            self.subscriptions.insert(account, &expires_at);
+            self.env().emit_event(Subscribed {
+                account,
+                expires_at,
+            });
+            Ok(expires_at)
+        }
+
+        /// Returns the block at which the subscription of `account` ends, if
+        /// it ever subscribed
+        #[ink(message)]
+        pub fn subscription_of(&self, account: AccountId) -> Option<BlockNumber> {
+            self.subscriptions.get(account)
+        }
+
+        /// Returns the fee of a flip with a referrer and the referrer's share
+        /// of it in percent
+        #[ink(message)]
+        pub fn referral_terms(&self) -> (Balance, u8) {
+            self.referral_terms.get().unwrap_or_default()
+        }
+
+        /// Sets the fee of a flip with a referrer and the referrer's share of
+        /// it in percent, a zero fee disables referrals.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_referral_terms(&mut self, fee: Balance, share_percent: u8) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_referral_terms"))?;
+            if share_percent > 100 {
+                return Err(Error::InvalidConfig);
+            }
+            self.referral_terms.set(&(fee, share_percent));
+            Ok(())
+        }
+
+        /// Flips on the caller's behalf and credits `referrer` with its share
+        /// of the transferred value, which has to cover the fee.
+        ///
+        /// The rest of the value goes to the fee pool. Returns the reward
+        /// credited to `referrer`.
+        #[ink(message, payable)]
+        pub fn flip_with_referrer(&mut self, referrer: AccountId) -> Result<Balance> {
+            let (fee, share_percent) = self.referral_terms();
+            if fee == 0 {
+                return Err(Error::ReferralsDisabled);
+            }
+            let caller = self.env().caller();
+            if referrer == caller {
+                return Err(Error::SelfReferral);
+            }
+            let paid = self.env().transferred_value();
+            if paid < fee {
+                return Err(Error::InsufficientPayment);
+            }
+
+            let reward = paid / 100 * Balance::from(share_percent)
+                + paid % 100 * Balance::from(share_percent) / 100;
+            let rewards = self
+                .referral_rewards_of(referrer)
+                .checked_add(reward)
+                .ok_or(Error::Overflow)?;
+            let total = self
+                .referral_rewards_total
+                .get()
+                .unwrap_or_default()
+                .checked_add(reward)
+                .ok_or(Error::Overflow)?;
+            self.credit_fee_pool(paid - reward)?;
+            self.referral_rewards.insert(referrer, &rewards);
+            self.referral_rewards_total.set(&total);
+
+            self.flip_for(caller)?;
+            Ok(reward)
+        }
+
+        /// Returns the referral rewards `account` can claim
+        #[ink(message)]
+        pub fn referral_rewards_of(&self, account: AccountId) -> Balance {
+            self.referral_rewards.get(account).unwrap_or_default()
+        }
+
+        /// Pays the caller's referral rewards out to them.
+        ///
+        /// Returns the amount paid.
+        #[ink(message)]
+        pub fn claim_referral_rewards(&mut self) -> Result<Balance> {
+            let caller = self.env().caller();
+            let rewards = self.referral_rewards_of(caller);
+            if rewards == 0 {
+                return Err(Error::NothingToClaim);
+            }
+            self.ensure_transferable(rewards)?;
+            self.referral_rewards.remove(caller);
+            self.referral_rewards_total.set(
+                &self
+                    .referral_rewards_total
+                    .get()
+                    .unwrap_or_default()
+                    .saturating_sub(rewards),
+            );
+            self.env().transfer(caller, rewards).map_err(Error::from)?;
+            Ok(rewards)
+        }
+    }
+
+    /// Auctions for the next flip.
+    ///
+    /// Only part of builds with the `auctions` feature.
+    impl CrossContractFlipper {
+        /// Opens an auction for the next flip, taking bids for `duration`
+        /// blocks.
+        ///
+        /// The winner of the previous auction has to have claimed their flip,
+        /// or the auction has to have been settled with `settle_auction`.
+        /// Only callable by the owner.
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn start_auction(&mut self, duration: BlockNumber) -> Result<u32> {
+            self.ensure_owner_and_log(ink::selector_bytes!("start_auction"))?;
+            if duration == 0 {
+                return Err(Error::InvalidConfig);
+            }
+            let now = self.env().block_number();
+            let id = match self.auction.get() {
+                Some(auction) if now < auction.ends_at => return Err(Error::AuctionStillOpen),
+                Some(auction) if auction.highest.is_some() && !auction.claimed => {
+                    return Err(Error::AuctionUnsettled)
+                }
+                Some(auction) => auction.id.checked_add(1).ok_or(Error::Overflow)?,
+                None => 0,
+            };
+            self.auction.set(&Auction {
+                id,
+                ends_at: now.saturating_add(duration),
+                highest: None,
+                claimed: false,
+            });
+            Ok(id)
+        }
+
+        /// Returns the current or last auction, if any
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn auction(&self) -> Option<Auction> {
+            self.auction.get()
+        }
+
+        /// Adds the transferred value to the caller's bid in the running
+        /// auction; the total has to beat the highest bid.
+        #[cfg(feature = "auctions")]
+        #[ink(message, payable)]
+        pub fn bid(&mut self) -> Result<()> {
+            let mut auction = self.auction.get().ok_or(Error::AuctionNotActive)?;
+            if self.env().block_number() >= auction.ends_at {
+                return Err(Error::AuctionNotActive);
+            }
+            let bidder = self.env().caller();
+            let total = self
+                .bid_of(auction.id, bidder)
+                .checked_add(self.env().transferred_value())
+                .ok_or(Error::Overflow)?;
+            if matches!(auction.highest, Some((_, highest)) if total <= highest) {
+                return Err(Error::BidTooLow);
+            }
+            let bids_total = self
+                .bids_total
+                .get()
+                .unwrap_or_default()
+                .checked_add(self.env().transferred_value())
+                .ok_or(Error::Overflow)?;
+            self.bids.insert((auction.id, bidder), &total);
+            self.bids_total.set(&bids_total);
+            auction.highest = Some((bidder, total));
+            self.auction.set(&auction);
+            self.env().emit_event(BidPlaced {
+                auction: auction.id,
+                bidder,
+                total,
+            });
+            Ok(())
+        }
+
+        /// Returns the total `account` has bid in auction `auction_id` and
+        /// not been paid back yet
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn bid_of(&self, auction_id: u32, account: AccountId) -> Balance {
+            self.bids.get((auction_id, account)).unwrap_or_default()
+        }
+
+        /// Performs the flip won in the closed auction.
+        ///
+        /// The winning bid goes to the fee pool.
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn claim_auction_flip(&mut self) -> Result<()> {
+            let mut auction = self.closed_auction()?;
+            let caller = self.env().caller();
+            let (winner, bid) = auction
+                .highest
+                .filter(|(winner, _)| *winner == caller && !auction.claimed)
+                .ok_or(Error::NotAuctionWinner)?;
+            auction.claimed = true;
+            self.auction.set(&auction);
+            self.credit_fee_pool(bid)?;
+            self.release_bid(auction.id, winner, bid);
+            self.flip_for(winner)
+        }
+
+        /// Settles the closed auction once its winner let
+        /// `AUCTION_CLAIM_PERIOD` blocks pass without claiming the flip.
+        ///
+        /// The winning bid goes to the fee pool, but nobody flips, so the next
+        /// auction can start. Callable by anyone.
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn settle_auction(&mut self) -> Result<()> {
+            let mut auction = self.closed_auction()?;
+            let (winner, bid) = auction
+                .highest
+                .filter(|_| !auction.claimed)
+                .ok_or(Error::NothingToSettle)?;
+            let deadline = auction.ends_at.saturating_add(AUCTION_CLAIM_PERIOD);
+            if self.env().block_number() < deadline {
+                return Err(Error::ClaimPeriodOpen);
+            }
+            auction.claimed = true;
+            self.auction.set(&auction);
+            self.credit_fee_pool(bid)?;
+            self.release_bid(auction.id, winner, bid);
+            self.env().emit_event(AuctionSettled {
+                auction: auction.id,
+                winner,
+                bid,
+            });
+            Ok(())
+        }
+
+        /// Pays the caller's losing bid in auction `auction_id` back, once
+        /// that auction closed.
+        ///
+        /// Bids in earlier auctions can be reclaimed at any time.
+        #[cfg(feature = "auctions")]
+        #[ink(message)]
+        pub fn reclaim_bid(&mut self, auction_id: u32) -> Result<Balance> {
+            let auction = self.auction.get().ok_or(Error::AuctionNotActive)?;
+            let caller = self.env().caller();
+            if auction_id == auction.id {
+                self.closed_auction()?;
+                if matches!(auction.highest, Some((winner, _)) if winner == caller) {
+                    return Err(Error::NothingToReclaim);
+                }
+            }
+            let bid = self
+                .bids
+                .get((auction_id, caller))
+                .ok_or(Error::NothingToReclaim)?;
+            // The bidder's account existed when it paid, so any refund works.
+            self.ensure_balance(bid)?;
+            self.release_bid(auction_id, caller, bid);
+            self.env()
+                .transfer(caller, bid)
+                .map_err(|_| Error::TransferFailed)?;
+            Ok(bid)
+        }
+
+        /// Removes `bidder`'s escrowed `bid` in auction `auction_id`.
+        #[cfg(feature = "auctions")]
+        fn release_bid(&mut self, auction_id: u32, bidder: AccountId, bid: Balance) {
+            self.bids.remove((auction_id, bidder));
+            self.bids_total.set(
+                &self
+                    .bids_total
+                    .get()
+                    .unwrap_or_default()
+                    .saturating_sub(bid),
+            );
+        }
+
+        #[cfg(feature = "auctions")]
+        fn closed_auction(&self) -> Result<Auction> {
+            let auction = self.auction.get().ok_or(Error::AuctionNotActive)?;
+            if self.env().block_number() < auction.ends_at {
+                return Err(Error::AuctionStillOpen);
+            }
+            Ok(auction)
+        }
+    }
+
+    impl CrossContractFlipper {
+        /// Returns the Merkle root of the allowlist, if any
+        #[ink(message)]
+        pub fn allowlist_root(&self) -> Option<Hash> {
+            self.allowlist_root.get()
+        }
+
+        /// Sets the Merkle root of the accounts allowed to `flip_with_proof`.
+        ///
+        /// Leaves are the BLAKE2-256 hashes of the account ids, inner nodes
+        /// the hash of their two children in ascending order. Only callable
+        /// by the owner.
+        #[ink(message)]
+        pub fn set_allowlist_root(&mut self, root: Hash) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_allowlist_root"))?;
+            self.allowlist_root.set(&root);
+            Ok(())
+        }
+
+        /// Flips if `proof` shows the caller is on the allowlist.
+        ///
+        /// `proof` holds the sibling hashes from the caller's leaf up to the
+        /// root.
+        #[ink(message)]
+        pub fn flip_with_proof(&mut self, proof: Vec<[u8; 32]>) -> Result<()> {
+            let root = self.allowlist_root.get().ok_or(Error::AllowlistNotSet)?;
+            let caller = self.env().caller();
+            let leaf = self.env().hash_bytes::<Blake2x256>(caller.as_ref());
+            if !Self::verify_merkle_proof(root, leaf, &proof) {
+                return Err(Error::InvalidProof);
+            }
+            self.flip_for(caller)
+        }
+
+        fn verify_merkle_proof(root: Hash, leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+            let computed = proof.iter().fold(leaf, |node, sibling| {
+                let (left, right) = if node <= *sibling {
+                    (node, *sibling)
+                } else {
+                    (*sibling, node)
+                };
+                let mut output = [0; 32];
+                ink::env::hash_bytes::<Blake2x256>(&[left, right].concat(), &mut output);
+                output
+            });
+            Hash::from(computed) == root
+        }
+
+        /// Returns all tunables at once
+        #[ink(message)]
+        pub fn get_config(&self) -> Config {
+            self.config.get().unwrap_or_default()
+        }
+
+        /// Replaces all tunables at once, after validating them.
+        ///
+        /// The individual setters change a single tunable the same way. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn set_config(&mut self, config: Config) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_config"))?;
+            self.update_config(|current| *current = config)
+        }
+
+        fn update_config(&mut self, update: impl FnOnce(&mut Config)) -> Result<()> {
+            let mut config = self.get_config();
+            update(&mut config);
+            self.ensure_payable(config.relayer_fee)?;
+            self.ensure_payable(config.keeper_bounty)?;
+            config.validate()?;
+            self.config.set(&config);
+            Ok(())
+        }
+
+        /// Returns whether flipping is paused
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.get_config().paused
+        }
+
+        /// Pauses or unpauses flipping.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_paused"))?;
+            self.update_config(|config| config.paused = paused)
+        }
+
+        /// Returns whether the messages of `flag` can be called
+        #[ink(message)]
+        pub fn message_enabled(&self, flag: MessageFlag) -> bool {
+            self.get_config().disabled_messages & flag.bit() == 0
+        }
+
+        /// Switches the messages of `flag` on or off.
+        ///
+        /// Unlike pausing, this leaves all other messages working. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn set_message_enabled(&mut self, flag: MessageFlag, enabled: bool) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_message_enabled"))?;
+            self.update_config(|config| {
+                if enabled {
+                    config.disabled_messages &= !flag.bit();
+                } else {
+                    config.disabled_messages |= flag.bit();
+                }
+            })
+        }
+
+        fn ensure_message_enabled(&self, flag: MessageFlag) -> Result<()> {
+            if !self.message_enabled(flag) {
+                return Err(Error::MessageDisabled);
+            }
+            Ok(())
+        }
+
+        /// Returns whether `account` is frozen
+        #[ink(message)]
+        pub fn is_frozen(&self, account: AccountId) -> bool {
+            self.frozen.contains(account)
+        }
+
+        /// Freezes or unfreezes `account`.
+        ///
+        /// Unlike pausing, this only affects one account: no flips can be
+        /// attributed to it and it can't register session keys or commit
+        /// until it is unfrozen, while everything it has stays as it is.
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_frozen(&mut self, account: AccountId, frozen: bool) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_frozen"))?;
+            if frozen == self.is_frozen(account) {
+                return Ok(());
+            }
+            if frozen {
+                self.frozen.insert(account, &());
+            } else {
+                self.frozen.remove(account);
+            }
+            self.env().emit_event(AccountFreeze { account, frozen });
+            Ok(())
+        }
+
+        fn ensure_not_frozen(&self, account: AccountId) -> Result<()> {
+            if self.is_frozen(account) {
+                return Err(Error::AccountFrozen);
+            }
+            Ok(())
+        }
+
+        /// Returns whether flips are rejected unless the caller is the origin
+        #[ink(message)]
+        pub fn origin_only(&self) -> bool {
+            self.get_config().origin_only
+        }
+
+        /// Rejects flips initiated by other contracts when `origin_only` is
+        /// set, so only accounts signing the transaction themselves can flip.
+        ///
+        /// Like pausing, this doesn't affect `call_delegate_flip`. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn set_origin_only(&mut self, origin_only: bool) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_origin_only"))?;
+            self.update_config(|config| config.origin_only = origin_only)
+        }
+
+        /// Flips on the caller's behalf and attaches `memo` to the `Flipped`
+        /// event, e.g. to correlate the flip with an off-chain order.
+        ///
+        /// The memo is only emitted, not stored, and can be up to
+        /// `max_memo_len` bytes long.
+        #[ink(message)]
+        pub fn flip_with_memo(&mut self, memo: Vec<u8>) -> Result<()> {
+            if memo.len() > self.get_config().max_memo_len as usize {
+                return Err(Error::MemoTooLong);
+            }
+            self.flip_with_memo_for(self.env().caller(), Some(memo))
+        }
+
+        /// Flips the value through the delegate and attributes the flip to
+        /// `account`.
+        ///
+        /// This is the path every flip made on someone's behalf goes through,
+        /// so per-account limits are enforced here.
+        fn flip_for(&mut self, account: AccountId) -> Result<()> {
+            self.flip_with_memo_for(account, None)
+        }
+
+        fn flip_with_memo_for(&mut self, account: AccountId, memo: Option<Vec<u8>>) -> Result<()> {
+            self.admit_flips(account, 1)?;
+            self.delegate_flip()?;
+            self.commit_flips(account, 1);
+            self.record_flip_by(account);
+            self.env().emit_event(Flipped {
+                by: account,
+                value: self.root.value,
+                memo,
+            });
+            Ok(())
+        }
+
+        /// Flips `count` times on the caller's behalf and emits a single
+        /// `FlippedBatch` event instead of one `Flipped` event per flip.
+        ///
+        /// The batch counts as one flip for the cooldown and as `count` flips
+        /// for the per-block limit. Returns the value after the last flip.
+        #[ink(message)]
+        pub fn flip_n(&mut self, count: u32) -> Result<bool> {
+            self.ensure_message_enabled(MessageFlag::FlipN)?;
+            if count == 0 || count > MAX_BATCH_FLIPS {
+                return Err(Error::InvalidFlipCount);
+            }
+            let caller = self.env().caller();
+            self.admit_flips(caller, count)?;
+            let mut first_value = None;
+            for _ in 0..count {
+                self.delegate_flip()?;
+                self.record_flip_by(caller);
+                first_value.get_or_insert(self.root.value);
+            }
+            self.commit_flips(caller, count);
+            self.env().emit_event(FlippedBatch {
+                by: caller,
+                count,
+                first_value: first_value.unwrap_or(self.root.value),
+                last_value: self.root.value,
+            });
+            Ok(self.root.value)
+        }
+
+        /// Checks the per-account and per-block limits for `count` flips by
+        /// `account`.
+        ///
+        /// Only checks, so a flip that fails afterwards leaves nothing
+        /// behind; `commit_flips` accounts for the flips once they were made.
+        fn admit_flips(&self, account: AccountId, count: u32) -> Result<()> {
+            let config = self.get_config();
+            if config.paused {
+                return Err(Error::Paused);
+            }
+            if self.confirmation_terms.get().unwrap_or_default().0 > 0 {
+                return Err(Error::ConfirmationRequired);
+            }
+            self.ensure_not_frozen(account)?;
+            if config.origin_only && !self.env().caller_is_origin() {
+                return Err(Error::CallerNotOrigin);
+            }
+            let now = self.env().block_number();
+            let (price, _) = self.subscription_terms();
+            if price > 0 && self.subscriptions.get(account).unwrap_or_default() <= now {
+                return Err(Error::SubscriptionRequired);
+            }
+
+            let cooldown = config.cooldown;
+            let last = self.last_flip_at.get(account);
+            if let Some(last) = last {
+                let until = last.saturating_add(cooldown);
+                if cooldown > 0 && now < until {
+                    return Err(Error::CooldownActive { until });
+                }
+            }
+            let flips = self.flips_in_current_block();
+            let max = config.max_flips_per_block;
+            if max > 0 && flips.saturating_add(count) > max {
+                return Err(Error::BlockFlipLimitReached);
+            }
+            Ok(())
+        }
+
+        /// Starts `account`'s cooldown and counts `count` flips against the
+        /// block's limit, once `admit_flips` let them through and they were
+        /// made.
+        fn commit_flips(&mut self, account: AccountId, count: u32) {
+            let now = self.env().block_number();
+            if self.last_flip_at.get(account) != Some(now) {
+                self.last_flip_at.insert(account, &now);
+            }
+            let flips = self.flips_in_current_block();
+            self.block_flips.set(&(now, flips.saturating_add(count)));
+        }
+
+        /// Runs the delegate's `flip` against this contract's storage and
+        /// returns the gas (`ref_time`) it used once it is done, unlike the
+        /// tail call of `DelegateMode::TailCall`.
+        fn delegate_flip(&mut self) -> Result<u64> {
+            self.delegate_flip_with_ttl(self.flip_ttl())
+        }
+
+        /// Like `delegate_flip`, but the flip holds for `ttl` blocks.
+        fn delegate_flip_with_ttl(&mut self, ttl: BlockNumber) -> Result<u64> {
+            let delegate = self.delegate()?;
+            self.settle_value();
+            let before = self.root.value;
+            let selector = ink::selector_bytes!("flip");
+            let gas_before = self.env().gas_left();
+            let result = build_call::<Environment>()
+                .delegate(delegate)
+                .exec_input(ExecutionInput::new(Selector::new(selector)))
+                .returns::<()>()
+                .try_invoke();
+            let gas_used = gas_before.saturating_sub(self.env().gas_left());
+            self.record_delegate_gas(gas_used);
+            self.record_call(selector, matches!(result, Ok(Ok(_))));
+            trace!("delegate_flip: delegate={:?} -> {:?}", delegate, result);
+            result
+                .map_err(|_| Error::DelegateCallFailed)?
+                .map_err(|_| Error::DelegateCallFailed)?;
+            self.count_flip();
+            self.reload_value();
+            self.arm_expiry(before, self.root.value, ttl);
+            self.open_veto_window(before);
+            self.env().emit_event(DelegateCalled {
+                target: delegate,
+                gas_used,
+            });
+            Ok(gas_used)
+        }
+
+        /// Returns the number of blocks a flip holds before the value reverts,
+        /// or 0 if flips hold for good
+        #[ink(message)]
+        pub fn flip_ttl(&self) -> BlockNumber {
+            self.get_config().flip_ttl
+        }
+
+        /// Makes flips revert `ttl` blocks after the latest one, zero keeps
+        /// them for good.
+        ///
+        /// Only affects flips made afterwards. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_flip_ttl(&mut self, ttl: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_flip_ttl"))?;
+            self.update_config(|config| config.flip_ttl = ttl)
+        }
+
+        /// Returns the longest number of blocks callers can make a flip hold
+        /// for with `flip_with_ttl`
+        #[ink(message)]
+        pub fn max_flip_ttl(&self) -> BlockNumber {
+            self.get_config().max_flip_ttl
+        }
+
+        /// Sets the longest number of blocks callers can make a flip hold
+        /// for, zero doesn't let them choose.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_max_flip_ttl(&mut self, max: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_max_flip_ttl"))?;
+            self.update_config(|config| config.max_flip_ttl = max)
+        }
+
+        /// Flips on the caller's behalf, holding for `ttl` blocks instead of
+        /// `flip_ttl`, e.g. for a temporary feature toggle.
+        ///
+        /// `ttl` can't exceed `max_flip_ttl`; `None` uses `flip_ttl`.
+        #[ink(message)]
+        pub fn flip_with_ttl(&mut self, ttl: Option<BlockNumber>) -> Result<()> {
+            let ttl = match ttl {
+                Some(ttl) if ttl == 0 || ttl > self.max_flip_ttl() => {
+                    return Err(Error::InvalidTtl)
+                }
+                Some(ttl) => ttl,
+                None => self.flip_ttl(),
+            };
+            let caller = self.env().caller();
+            self.admit_flips(caller, 1)?;
+            self.delegate_flip_with_ttl(ttl)?;
+            self.commit_flips(caller, 1);
+            self.record_flip_by(caller);
+            self.env().emit_event(Flipped {
+                by: caller,
+                value: self.root.value,
+                memo: None,
+            });
+            Ok(())
+        }
+
+        /// Returns the block at which the value reverts, if the latest flip
+        /// hasn't expired yet
+        #[ink(message)]
+        pub fn expires_at(&self) -> Option<BlockNumber> {
+            self.pending_expiry().map(|expiry| expiry.at)
+        }
+
+        fn pending_expiry(&self) -> Option<Expiry> {
+            self.expiry
+                .get()
+                .flatten()
+                .filter(|expiry| self.env().block_number() < expiry.at)
+        }
+
+        /// Returns the guardian, who can veto flips, if any
+        #[ink(message)]
+        pub fn guardian(&self) -> Option<AccountId> {
+            self.guardian.get()
+        }
+
+        /// Sets the guardian, who can veto flips within `veto_window`.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_guardian(&mut self, guardian: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_guardian"))?;
+            self.guardian.set(&guardian);
+            Ok(())
+        }
+
+        /// Returns the number of blocks the guardian can veto a flip in
+        #[ink(message)]
+        pub fn veto_window(&self) -> BlockNumber {
+            self.get_config().veto_window
+        }
+
+        /// Sets the number of blocks the guardian can veto a flip in, zero
+        /// makes flips final right away.
+        ///
+        /// Only affects flips made afterwards. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_veto_window(&mut self, window: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_veto_window"))?;
+            self.update_config(|config| config.veto_window = window)
+        }
+
+        /// Returns whether the latest flip can still be vetoed, is final or
+        /// was vetoed, or `None` if it was made without a veto window
+        #[ink(message)]
+        pub fn last_flip_status(&self) -> Option<FlipStatus> {
+            self.last_flip().map(|last| last.status)
+        }
+
+        fn last_flip(&self) -> Option<LastFlip> {
+            let mut last = self.last_flip.get().flatten()?;
+            let now = self.env().block_number();
+            if matches!(last.status, FlipStatus::Pending { final_at } if now >= final_at) {
+                last.status = FlipStatus::Final;
+            }
+            Some(last)
+        }
+
+        /// Reverts the latest flip while its veto window is open, restoring the
+        /// value from before it.
+        ///
+        /// Flips made earlier stay, even if their window is still open. Only
+        /// callable by the guardian.
+        #[ink(message)]
+        pub fn veto_last_flip(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.guardian() != Some(caller) {
+                return Err(Error::NotGuardian);
+            }
+            let mut last = self.last_flip().ok_or(Error::NothingToVeto)?;
+            if !matches!(last.status, FlipStatus::Pending { .. }) {
+                return Err(Error::NothingToVeto);
+            }
+            last.status = FlipStatus::Vetoed;
+            self.last_flip.set(&Some(last));
+            self.expiry.set(&None);
+            self.write_value(last.before);
+            self.env().emit_event(FlipVetoed {
+                guardian: caller,
+                value: last.before,
+            });
+            Ok(())
+        }
+
+        /// Records a flip away from `before` as the latest one, for the
+        /// guardian to veto.
+        ///
+        /// Without a veto window, only a flip still pending is forgotten, so
+        /// flips don't pay for a write nobody needs.
+        fn open_veto_window(&mut self, before: bool) {
+            let window = self.veto_window();
+            if window == 0 {
+                if self.last_flip.get().flatten().is_some() {
+                    self.last_flip.set(&None);
+                }
+                return;
+            }
+            let final_at = self.env().block_number().saturating_add(window);
+            self.last_flip.set(&Some(LastFlip {
+                before,
+                status: FlipStatus::Pending { final_at },
+            }));
+        }
+
+        /// Writes the value back if a new epoch started or the latest flip
+        /// expired, so flips and the delegate see the value `get` reports.
+        fn settle_value(&mut self) {
+            if let Some(value) = self.epoch_reset() {
+                self.value_epoch.set(&self.current_epoch());
+                if self.expiry.get().flatten().is_some() {
+                    self.expiry.set(&None);
+                }
+                return self.write_value(value);
+            }
+            let Some(expiry) = self.expiry.get().flatten() else {
+                return;
+            };
+            if self.env().block_number() < expiry.at {
+                return;
+            }
+            self.expiry.set(&None);
+            self.write_value(expiry.reverts_to);
+        }
+
+        /// Sets the value and writes it to the root cell right away, where
+        /// the delegate reads it from.
+        fn write_value(&mut self, value: bool) {
+            self.root.value = value;
+            ink::env::set_contract_storage(
+                &<Self as ink::storage::traits::StorageKey>::KEY,
+                &self.root,
+            );
+        }
+
+        /// Returns the number of blocks in an epoch and the value the
+        /// contract resets to when one starts, a zero length disables epochs
+        #[ink(message)]
+        pub fn epoch_config(&self) -> (BlockNumber, bool) {
+            self.epoch_config.get().unwrap_or_default()
+        }
+
+        /// Splits time into epochs of `length` blocks, at the start of which
+        /// the value resets to `reset_value`. A zero length disables epochs.
+        ///
+        /// The current value holds until the next epoch. Only callable by the
+        /// owner.
+        #[ink(message)]
+        pub fn set_epoch_config(&mut self, length: BlockNumber, reset_value: bool) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_epoch_config"))?;
+            self.settle_value();
+            self.epoch_config.set(&(length, reset_value));
+            self.value_epoch.set(&self.current_epoch());
+            Ok(())
+        }
+
+        /// Returns the current epoch, which is always 0 without epochs
+        #[ink(message)]
+        pub fn current_epoch(&self) -> BlockNumber {
+            let (length, _) = self.epoch_config();
+            if length == 0 {
+                return 0;
+            }
+            self.env().block_number() / length
+        }
+
+        /// Returns the attributed flips made in `epoch`
+        #[ink(message)]
+        pub fn epoch_stats(&self, epoch: BlockNumber) -> EpochStats {
+            self.epoch_stats.get(epoch).unwrap_or_default()
+        }
+
+        /// Returns the number of flips attributed to `account` in `epoch`
+        #[ink(message)]
+        pub fn epoch_flips_of(&self, epoch: BlockNumber, account: AccountId) -> u64 {
+            self.epoch_flips.get((epoch, account)).unwrap_or_default()
+        }
+
+        /// Returns the value to reset to if the value was last set in an
+        /// earlier epoch.
+        fn epoch_reset(&self) -> Option<bool> {
+            let (length, reset_value) = self.epoch_config();
+            let stale =
+                length > 0 && self.value_epoch.get().unwrap_or_default() < self.current_epoch();
+            stale.then_some(reset_value)
+        }
+
+        fn record_epoch_flip(&mut self, account: AccountId) {
+            if self.epoch_config().0 == 0 {
+                return;
+            }
+            let epoch = self.current_epoch();
+            let flips = self.epoch_flips_of(epoch, account);
+            let mut stats = self.epoch_stats(epoch);
+            if flips == 0 {
+                stats.flippers = stats.flippers.saturating_add(1);
+            }
+            stats.flips = stats.flips.saturating_add(1);
+            self.epoch_flips
+                .insert((epoch, account), &flips.saturating_add(1));
+            self.epoch_stats.insert(epoch, &stats);
+        }
+
+        /// Starts the expiry of a flip from `before` to `after` that holds
+        /// for `ttl` blocks, or for good if `ttl` is zero.
+        ///
+        /// The value falls back to what it was before the first of a run of
+        /// flips, and flipping back to it cancels the expiry.
+        fn arm_expiry(&mut self, before: bool, after: bool, ttl: BlockNumber) {
+            let reverts_to = self
+                .pending_expiry()
+                .map_or(before, |expiry| expiry.reverts_to);
+            if ttl == 0 || after == reverts_to {
+                if self.expiry.get().flatten().is_some() {
+                    self.expiry.set(&None);
+                }
+                return;
+            }
+            self.expiry.set(&Some(Expiry {
+                at: self.env().block_number().saturating_add(ttl),
+                reverts_to,
+            }));
+        }
+
+        /// Picks up the value a delegate wrote to the root cell directly,
+        /// before ink! writes `self` back at the end of the message.
+        fn reload_value(&mut self) {
+            if let Ok(Some(root)) = ink::env::get_contract_storage::<_, FlipperRoot>(
+                &<Self as ink::storage::traits::StorageKey>::KEY,
+            ) {
+                self.root = root;
+            }
+        }
+
+        /// Folds `used` into the rolling average, weighting it by
+        /// 1 / `DELEGATE_GAS_SMOOTHING`.
+        fn record_delegate_gas(&mut self, used: u64) {
+            let average = match self.delegate_gas.get() {
+                Some(average) => {
+                    average - average / DELEGATE_GAS_SMOOTHING + used / DELEGATE_GAS_SMOOTHING
+                }
+                None => used,
+            };
+            self.delegate_gas.set(&average);
+        }
+
+        /// Returns the rolling average of the gas (`ref_time`) the delegate's
+        /// `flip` used, or 0 before the first flip
+        ///
+        /// Only flips that return here are measured, which excludes the tail
+        /// call of `DelegateMode::TailCall`.
+        #[ink(message)]
+        pub fn avg_delegate_gas(&self) -> u64 {
+            self.delegate_gas.get().unwrap_or_default()
+        }
+
+        fn count_flip(&mut self) {
+            let count = self.flip_count();
+            self.flip_count.set(&count.saturating_add(1));
+        }
+
+        /// Returns the number of flips executed through the delegate
+        #[ink(message)]
+        pub fn flip_count(&self) -> Counter {
+            self.flip_count.get().unwrap_or_default()
+        }
+
+        fn record_flip_by(&mut self, account: AccountId) {
+            let flips = self.stats_of(account);
+            if flips == 0 {
+                let flippers = self.flippers.get().unwrap_or_default();
+                self.flippers.set(&flippers.saturating_add(1));
+            }
+            self.flips_by.insert(account, &flips.saturating_add(1));
+            let attributed = self.attributed_flips.get().unwrap_or_default();
+            self.attributed_flips.set(&attributed.saturating_add(1));
+            self.record_history(account);
+            self.record_epoch_flip(account);
+            let score = self.score_of(account).saturating_add(1);
+            self.scores.insert(account, &(score, self.decay_index()));
+            self.update_leaderboard(account, score);
+            self.update_streak(account);
+
+            let flips = flips.saturating_add(1);
+            if self.badge_milestones().contains(&flips) {
+                // A failed mint doesn't hold up the flip, the badge can still
+                // be claimed with `claim_badges`.
+                let _ = self.mint_badge(account, flips);
+            }
+        }
+
+        fn update_streak(&mut self, account: AccountId) {
+            let streak = match self.current_streak() {
+                Some(streak) if streak.account == account => Streak {
+                    account,
+                    length: streak.length.saturating_add(1),
+                },
+                // Another account flipped, which ends the previous streak.
+                _ => Streak { account, length: 1 },
+            };
+            self.current_streak.set(&streak);
+            if self
+                .longest_streak()
+                .is_none_or(|longest| longest.length < streak.length)
+            {
+                self.longest_streak.set(&streak);
+            }
+        }
+
+        /// Returns the account that made the latest attributed flips and
+        /// how many of them it made in a row, if anyone flipped yet
+        #[ink(message)]
+        pub fn current_streak(&self) -> Option<Streak> {
+            self.current_streak.get()
+        }
+
+        /// Returns the PSP34 contract minting achievement badges, if any
+        #[ink(message)]
+        pub fn badge_contract(&self) -> Option<AccountId> {
+            self.badge_contract.get()
+        }
+
+        /// Mints achievement badges through the PSP34 `contract` from now on.
+        ///
+        /// It has to implement `PSP34Mintable::mint` and let this contract
+        /// mint. Only callable by the owner.
+        #[ink(message)]
+        pub fn set_badge_contract(&mut self, contract: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_badge_contract"))?;
+            self.ensure_contract(contract, None)?;
+            self.badge_contract.set(&contract);
+            Ok(())
+        }
+
+        /// Returns the flip counts that earn a badge
+        #[ink(message)]
+        pub fn badge_milestones(&self) -> Vec<u64> {
+            self.badge_milestones.get().unwrap_or_default()
+        }
+
+        /// Sets the flip counts that earn a badge, which have to be nonzero
+        /// and strictly increasing.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_badge_milestones(&mut self, milestones: Vec<u64>) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_badge_milestones"))?;
+            if milestones.len() > MAX_BADGE_MILESTONES
+                || milestones.first() == Some(&0)
+                || milestones
+                    .windows(2)
+                    .any(|pair| matches!(pair, [a, b] if a >= b))
+            {
+                return Err(Error::InvalidConfig);
+            }
+            self.badge_milestones.set(&milestones);
+            Ok(())
+        }
+
+        /// Returns whether `account` was minted the badge for `milestone`
+        #[ink(message)]
+        pub fn has_badge(&self, account: AccountId, milestone: u64) -> bool {
+            self.badges.contains((account, milestone))
+        }
+
+        /// Mints the badges of every milestone the caller reached but wasn't
+        /// minted yet, e.g. because the badge contract was set afterwards.
+        ///
+        /// Returns the number of badges minted.
+        #[ink(message)]
+        pub fn claim_badges(&mut self) -> Result<u32> {
+            let caller = self.env().caller();
+            let flips = self.stats_of(caller);
+            let mut minted = 0;
+            for milestone in self.badge_milestones() {
+                if milestone > flips {
+                    break;
+                }
+                if !self.has_badge(caller, milestone) {
+                    self.mint_badge(caller, milestone)?;
+                    minted += 1;
+                }
+            }
+            Ok(minted)
+        }
+
+        /// Mints the badge for `milestone` to `account`, unless it was
+        /// minted already.
+        fn mint_badge(&mut self, account: AccountId, milestone: u64) -> Result<()> {
+            if self.has_badge(account, milestone) {
+                return Ok(());
+            }
+            let contract = self.badge_contract().ok_or(Error::BadgeContractNotSet)?;
+            // Unique per account and milestone, so retries can't mint twice.
+            let id = (PSP34_ID_BYTES, (account, milestone).encode());
+            let selector = ink::selector_bytes!("PSP34Mintable::mint");
+            let result = build_call::<Environment>()
+                .call(contract)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(selector))
+                        .push_arg(account)
+                        .push_arg(&id),
+                )
+                // Any `PSP34Error` is as good as another here.
+                .returns::<core::result::Result<(), RawOutput>>()
+                .try_invoke();
+            self.record_call(selector, matches!(result, Ok(Ok(Ok(())))));
+            trace!(
+                "mint_badge: account={:?} milestone={} -> {:?}",
+                account,
+                milestone,
+                result
+            );
+            if !matches!(result, Ok(Ok(Ok(())))) {
+                return Err(Error::BadgeMintFailed);
+            }
+            self.badges.insert((account, milestone), &());
+            Ok(())
+        }
+
+        /// Returns the longest run of consecutive attributed flips by one
+        /// account so far, if anyone flipped yet
+        ///
+        /// Ties keep the streak that got there first.
+        #[ink(message)]
+        pub fn longest_streak(&self) -> Option<Streak> {
+            self.longest_streak.get()
+        }
+
+        fn update_leaderboard(&mut self, account: AccountId, score: u64) {
+            // Decaying every entry by the same amount keeps them in order.
+            let mut leaderboard = self.leaderboard();
+            if let Some(entry) = leaderboard.iter_mut().find(|(a, _)| *a == account) {
+                entry.1 = score;
+            } else if leaderboard.len() < LEADERBOARD_SIZE {
+                leaderboard.push((account, score));
+            } else if leaderboard.last().is_some_and(|(_, last)| *last < score) {
+                leaderboard.pop();
+                leaderboard.push((account, score));
+            } else {
+                return;
+            }
+            // Stable, so accounts that got to a score first stay ahead.
+            leaderboard.sort_by(|(_, a), (_, b)| b.cmp(a));
+            self.leaderboard.set(&leaderboard);
+            let index = self.decay_index();
+            if index != self.leaderboard_index.get().unwrap_or_default() {
+                self.leaderboard_index.set(&index);
+            }
+        }
+
+        /// Returns the accounts with the highest scores and their scores,
+        /// highest first
+        ///
+        /// Without decay, the scores are the accounts' attributed flips.
+        #[ink(message)]
+        pub fn leaderboard(&self) -> Vec<(AccountId, u64)> {
+            let mut leaderboard = self.leaderboard.get().unwrap_or_default();
+            let index = self.leaderboard_index.get().unwrap_or_default();
+            for entry in &mut leaderboard {
+                entry.1 = self.decayed(entry.1, index);
+            }
+            leaderboard
+        }
+
+        /// Returns the number of blocks after which scores halve, or 0 if
+        /// they don't decay
+        #[ink(message)]
+        pub fn decay_period(&self) -> BlockNumber {
+            self.get_config().decay_period
+        }
+
+        /// Halves every score each `period` blocks, so the leaderboard
+        /// reflects recent activity. A zero period disables decay.
+        ///
+        /// Scores decay by the periods that passed since they were updated,
+        /// measured with the period in place when they are read. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn set_decay_period(&mut self, period: BlockNumber) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_decay_period"))?;
+            self.update_config(|config| config.decay_period = period)
+        }
+
+        /// Returns the leaderboard score of `account`: one point per
+        /// attributed flip, halved every `decay_period` blocks
+        #[ink(message)]
+        pub fn score_of(&self, account: AccountId) -> u64 {
+            self.scores
+                .get(account)
+                .map_or(0, |(score, index)| self.decayed(score, index))
+        }
+
+        /// Returns the number of decay periods since block 0.
+        fn decay_index(&self) -> BlockNumber {
+            let period = self.decay_period();
+            if period == 0 {
+                return 0;
+            }
+            self.env().block_number() / period
+        }
+
+        /// Decays `score`, last updated in decay period `index`, to now.
+        fn decayed(&self, score: u64, index: BlockNumber) -> u64 {
+            let halvings = self.decay_index().saturating_sub(index);
+            if halvings >= 64 {
+                return 0;
+            }
+            score >> halvings
+        }
+
+        /// Returns the number of flips attributed to `account`
+        #[ink(message)]
+        pub fn stats_of(&self, account: AccountId) -> u64 {
+            self.flips_by.get(account).unwrap_or_default()
+        }
+
+        /// Returns the flip statistics across all accounts
+        #[ink(message)]
+        pub fn global_stats(&self) -> GlobalStats {
+            GlobalStats {
+                total_flips: self.flip_count(),
+                attributed_flips: self.attributed_flips.get().unwrap_or_default(),
+                flippers: self.flippers.get().unwrap_or_default(),
+            }
+        }
+
+        fn record_history(&mut self, by: AccountId) {
+            let len = self.history_len.get().unwrap_or_default();
+            let record = FlipRecord {
+                block: self.env().block_number(),
+                by,
+                value: self.root.value,
+            };
+            self.history.insert(len % HISTORY_SIZE, &record);
+            self.history_len.set(&len.saturating_add(1));
+        }
+
+        /// Returns up to `limit` attributed flips from `cursor` on, or from
+        /// the oldest flip still kept if `cursor` is `None`
+        ///
+        /// Only the latest flips are kept, so a page may start after
+        /// `cursor` if the flips it pointed at were overwritten since.
+        #[ink(message)]
+        pub fn flip_history(&self, cursor: Option<HistoryCursor>, limit: u32) -> HistoryPage {
+            let len = self.history_len.get().unwrap_or_default();
+            let oldest = len.saturating_sub(HISTORY_SIZE);
+            let start = cursor.map_or(oldest, |HistoryCursor(position)| position.max(oldest));
+            let end = len.min(start.saturating_add(u64::from(limit.min(MAX_HISTORY_PAGE))));
+            let records = (start..end)
+                .filter_map(|position| self.history.get(position % HISTORY_SIZE))
+                .collect();
+            HistoryPage {
+                records,
+                next: HistoryCursor(end.max(start)),
+            }
+        }
+
+        /// Returns the state of each of `accounts`, in the same order, so
+        /// frontends can render a list in a single query
+        #[ink(message)]
+        pub fn get_many(&self, accounts: Vec<AccountId>) -> Result<Vec<AccountState>> {
+            if accounts.len() > MAX_BATCH_ACCOUNTS {
+                return Err(Error::TooManyAccounts);
+            }
+            Ok(accounts
+                .into_iter()
+                .map(|account| AccountState {
+                    account,
+                    flips: self.stats_of(account),
+                    nonce: self.nonces.get(account).unwrap_or_default(),
+                    last_flip_at: self.last_flip_at(account),
+                    subscribed_until: self.subscription_of(account),
+                    referral_rewards: self.referral_rewards_of(account),
+                    value: self.value_of(account),
+                })
+                .collect())
+        }
+
+        /// Returns the value kept for `account`, `false` unless set
+        #[ink(message)]
+        pub fn value_of(&self, account: AccountId) -> bool {
+            self.account_values.get(account).unwrap_or_default()
+        }
+
+        /// Sets the value of each account in `values`, e.g. to migrate them
+        /// or correct mistakes, and emits a single `ValuesSet` event.
+        ///
+        /// All or nothing: no value is set if any of the accounts is frozen.
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn batch_set(&mut self, values: Vec<(AccountId, bool)>) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("batch_set"))?;
+            if values.len() > MAX_BATCH_ACCOUNTS {
+                return Err(Error::TooManyAccounts);
+            }
+            for (account, _) in &values {
+                self.ensure_not_frozen(*account)?;
+            }
+            let mut set_true = 0;
+            for (account, value) in &values {
+                if *value {
+                    self.account_values.insert(account, &true);
+                    set_true += 1;
+                } else {
+                    self.account_values.remove(account);
+                }
+            }
+            self.env().emit_event(ValuesSet {
+                count: values.len() as u32,
+                set_true,
+            });
+            Ok(())
+        }
+
+        /// Returns up to `limit` items of the state from `offset` on, SCALE
+        /// encoded as a `Vec<StateItem>`, to be passed to `import_state` of a
+        /// redeployed instance
+        ///
+        /// Items are numbered the core state first, then members, selectors
+        /// and the kept history, so an empty chunk means everything was
+        /// exported. Mappings can't be enumerated, so per-account state has
+        /// to be moved with `batch_set` and the like. Only the flips of the
+        /// accounts in the kept history move along with it, and the importing
+        /// instance counts just these accounts as `flippers`. Only callable by
+        /// the owner.
+        #[ink(message)]
+        pub fn export_state(&self, offset: u32, limit: u32) -> Result<Vec<u8>> {
+            self.ensure_owner()?;
+            let offset = offset as usize;
+            let limit = limit.min(MAX_EXPORT_ITEMS) as usize;
+            let history_len = self.history_len.get().unwrap_or_default();
+            let core = StateItem::Core {
+                value: self.root.value,
+                flip_count: self.flip_count(),
+                attributed_flips: self.attributed_flips.get().unwrap_or_default(),
+                history_len,
+            };
+            let members = self.members.get().unwrap_or_default();
+            let names = self.selector_names();
+            let fixed = 1 + members.len() + names.len();
+            let mut items: Vec<_> = core::iter::once(core)
+                .chain(members.into_iter().map(StateItem::Member))
+                .chain(names.into_iter().filter_map(|name| {
+                    let selector = self.selectors.get(&name)?;
+                    Some(StateItem::Selector(name, selector))
+                }))
+                .skip(offset)
+                .take(limit)
+                .collect();
+
+            // Only read the history records that end up in the chunk.
+            let start = history_len
+                .saturating_sub(HISTORY_SIZE)
+                .saturating_add(offset.saturating_sub(fixed) as u64);
+            let end = history_len.min(start.saturating_add((limit - items.len()) as u64));
+            items.extend((start..end).filter_map(|position| {
+                let record = self.history.get(position % HISTORY_SIZE)?;
+                let flips = self.stats_of(record.by);
+                Some(StateItem::History(position, record, flips))
+            }));
+            Ok(items.encode())
+        }
+
+        /// Imports a chunk returned by `export_state` of another instance,
+        /// returning the number of items imported.
+        ///
+        /// Chunks can be imported in any order, but only until the instance
+        /// flips for the first time. Only callable by the owner.
+        #[ink(message)]
+        pub fn import_state(&mut self, chunk: Vec<u8>) -> Result<u32> {
+            self.ensure_owner_and_log(ink::selector_bytes!("import_state"))?;
+            if self.flip_count() != self.imported_flip_count.get().unwrap_or_default() {
+                return Err(Error::ImportClosed);
+            }
+            let items = Vec::<StateItem>::decode(&mut chunk.as_slice())
+                .map_err(|_| Error::InvalidStateChunk)?;
+            for item in &items {
+                match item.clone() {
+                    StateItem::Core {
+                        value,
+                        flip_count,
+                        attributed_flips,
+                        history_len,
+                    } => {
+                        self.root.value = value;
+                        self.flip_count.set(&flip_count);
+                        self.imported_flip_count.set(&flip_count);
+                        self.attributed_flips.set(&attributed_flips);
+                        self.extend_history_len(history_len);
+                    }
+                    StateItem::Member(account) => self.insert_member(account)?,
+                    StateItem::Selector(name, selector) => {
+                        self.register_selector(name, selector)?
+                    }
+                    StateItem::History(position, record, flips) => {
+                        self.history.insert(position % HISTORY_SIZE, &record);
+                        self.extend_history_len(position.saturating_add(1));
+                        if !self.flips_by.contains(record.by) {
+                            let flippers = self.flippers.get().unwrap_or_default();
+                            self.flippers.set(&flippers.saturating_add(1));
+                        }
+                        self.flips_by.insert(record.by, &flips);
+                    }
+                }
+            }
+            Ok(items.len() as u32)
+        }
+
+        /// Returns the previous instance the state was migrated from, if any
+        #[ink(message)]
+        pub fn migrated_from(&self) -> Option<AccountId> {
+            self.migrated_from.get()
+        }
+
+        /// Seeds this instance with the value, the counters and the owner of
+        /// the flipper at `old_contract`, read through its messages.
+        ///
+        /// Reads the narrower counters of instances of storage version 2 or
+        /// older as well. Going through messages rather than storage keys,
+        /// it also reads instances of version 3, whose fields sit at other
+        /// keys.
+        ///
+        /// Can only run once and only until the instance flips for the first
+        /// time. The old contract's owner, if it has one, becomes the owner of
+        /// this one, so call it last. Only callable by the owner.
+        #[ink(message)]
+        pub fn migrate_from(&mut self, old_contract: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("migrate_from"))?;
+            if self.migrated_from().is_some() {
+                return Err(Error::AlreadyMigrated);
+            }
+            if self.flip_count() != self.imported_flip_count.get().unwrap_or_default() {
+                return Err(Error::ImportClosed);
+            }
+            self.ensure_contract(old_contract, None)?;
+            let value = self.query(old_contract, ink::selector_bytes!("get"))?;
+            let version: Version = self.query(old_contract, ink::selector_bytes!("version"))?;
+            let (flip_count, stats) = if version.storage <= 2 {
+                let flip_count: u64 =
+                    self.query(old_contract, ink::selector_bytes!("flip_count"))?;
+                let stats: GlobalStatsV2 =
+                    self.query(old_contract, ink::selector_bytes!("global_stats"))?;
+                (Counter::from(flip_count), GlobalStats::from(stats))
+            } else {
+                (
+                    self.query(old_contract, ink::selector_bytes!("flip_count"))?,
+                    self.query(old_contract, ink::selector_bytes!("global_stats"))?,
+                )
+            };
+            let QueriedOwner(owner) = self.query(old_contract, ink::selector_bytes!("owner"))?;
+
+            self.root.value = value;
+            self.flip_count.set(&flip_count);
+            self.imported_flip_count.set(&flip_count);
+            self.attributed_flips.set(&stats.attributed_flips);
+            self.flippers.set(&stats.flippers);
+            if let Some(owner) = owner {
+                self.owner.set(&owner);
+            }
+            self.migrated_from.set(&old_contract);
+            Ok(())
+        }
+
+        /// Adds `account` to the governance members, if it isn't one yet.
+        ///
+        /// Part of every build, so imports keep the members even where
+        /// governance is left out.
+        fn insert_member(&mut self, account: AccountId) -> Result<()> {
+            let mut members = self.members.get().unwrap_or_default();
+            if members.contains(&account) {
+                return Ok(());
+            }
+            if members.len() >= MAX_MEMBERS {
+                return Err(Error::TooManyMembers);
+            }
+            members.push(account);
+            self.members.set(&members);
+            Ok(())
+        }
+
+        fn extend_history_len(&mut self, len: u64) {
+            if len > self.history_len.get().unwrap_or_default() {
+                self.history_len.set(&len);
+            }
+        }
+
+        /// Returns the contract and storage versions of this instance
+        #[ink(message)]
+        pub fn version(&self) -> Version {
+            Version {
+                contract: String::from(env!("CARGO_PKG_VERSION")),
+                storage: STORAGE_VERSION,
+            }
+        }
+
+        /// Checks whether the state can be moved to a new instance right now
+        /// and reports what is still pending, so operators can resolve it
+        /// before calling `export_state` or `migrate_from`
+        ///
+        /// Compare `storage_version` and `layout_hash` with the report of
+        /// the new instance to make sure it reads the same layout. The hash
+        /// changes whenever a field is added, removed or moved to another
+        /// key; changing a field's type has to bump the storage version
+        /// instead.
+        #[ink(message)]
+        pub fn validate_migration(&self) -> MigrationReport {
+            let now = self.env().block_number();
+            let next_proposal_id = self.next_proposal_id.get().unwrap_or_default();
+            let pending_proposals: Vec<u32> =
+                (next_proposal_id.saturating_sub(MAX_REPORTED_PROPOSALS)..next_proposal_id)
+                    .filter(|id| {
+                        self.proposals.get(id).is_some_and(|proposal| {
+                            !proposal.executed
+                                && now < proposal.executable_at
+                                && !self.is_revoked(*id, proposal.proposer)
+                        })
+                    })
+                    .collect();
+            let scheduled_flips = self.scheduled_flips().len() as u32;
+            let auction_pending = self.auction.get().is_some_and(|auction| {
+                now < auction.ends_at || (auction.highest.is_some() && !auction.claimed)
+            });
+            let confirmation_pending = self
+                .pending_confirmation
+                .get()
+                .flatten()
+                .is_some_and(|pending| now < pending.expires_at);
+            let veto_pending = matches!(self.last_flip_status(), Some(FlipStatus::Pending { .. }));
+            let expiry_pending = self.pending_expiry().is_some();
+            let paused = self.paused();
+            MigrationReport {
+                storage_version: STORAGE_VERSION,
+                layout_hash: self.layout_hash(),
+                ready: paused
+                    && pending_proposals.is_empty()
+                    && scheduled_flips == 0
+                    && !auction_pending
+                    && !confirmation_pending
+                    && !veto_pending
+                    && !expiry_pending,
+                pending_proposals,
+                scheduled_flips,
+                auction_pending,
+                confirmation_pending,
+                veto_pending,
+                expiry_pending,
+                owed: self.reserved_balance(),
+                paused,
+            }
+        }
+
+        /// Checks the counters, the history, the leaderboard and the lock
+        /// for consistency and returns every violation found, none if the
+        /// state is sound
+        ///
+        /// Run it after a migration or an import is complete, since chunks
+        /// imported in any order may break the invariants meanwhile. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn check_invariants(&self) -> Result<Vec<Violation>> {
+            self.ensure_owner()?;
+            let mut violations = Vec::new();
+            let stats = self.global_stats();
+            if stats.attributed_flips > stats.total_flips {
+                violations.push(Violation::AttributedAheadOfTotal {
+                    attributed_flips: stats.attributed_flips,
+                    total_flips: stats.total_flips,
+                });
+            }
+            if stats.flippers > stats.attributed_flips {
+                violations.push(Violation::FlippersAheadOfFlips {
+                    flippers: stats.flippers,
+                    attributed_flips: stats.attributed_flips,
+                });
+            }
+            let history_len = self.history_len.get().unwrap_or_default();
+            if Counter::from(history_len) > stats.attributed_flips {
+                violations.push(Violation::HistoryAheadOfFlips {
+                    history_len,
+                    attributed_flips: stats.attributed_flips,
+                });
+            }
+
+            let leaderboard = self.leaderboard.get().unwrap_or_default();
+            if leaderboard.len() > LEADERBOARD_SIZE {
+                violations.push(Violation::LeaderboardOverfull {
+                    len: leaderboard.len() as u32,
+                });
+            }
+            let unsorted = leaderboard
+                .windows(2)
+                .any(|pair| matches!(pair, [(_, a), (_, b)] if a < b));
+            let duplicate = leaderboard.iter().enumerate().any(|(index, (account, _))| {
+                leaderboard
+                    .iter()
+                    .take(index)
+                    .any(|(other, _)| other == account)
+            });
+            if unsorted || duplicate {
+                violations.push(Violation::LeaderboardUnsorted);
+            }
+            let sum = leaderboard.iter().fold(0, |sum: Counter, (_, score)| {
+                sum.saturating_add(Counter::from(*score))
+            });
+            if sum > stats.attributed_flips {
+                violations.push(Violation::LeaderboardAheadOfFlips {
+                    sum,
+                    attributed_flips: stats.attributed_flips,
+                });
+            }
+
+            if self.is_entered() {
+                violations.push(Violation::LockHeld);
+            }
+            Ok(violations)
+        }
+
+        /// Hashes the storage keys of all fields but the root cell's.
+        fn layout_hash(&self) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&self.field_keys()))
+        }
+
+        /// Returns the storage keys of all fields but the root cell's.
+        fn field_keys(&self) -> Vec<ink::primitives::Key> {
+            use ink::storage::traits::StorageKey;
+
+            // Destructured, so a new field doesn't compile until it's added.
+            let Self {
+                root: _,
+                delegate_to,
+                owner,
+                xcm_config,
+                nonces,
+                fee_pool,
+                session_keys,
+                commitments,
+                oracle,
+                scheduled_flips,
+                next_schedule_id,
+                last_flip_at,
+                block_flips,
+                subscription_terms,
+                subscriptions,
+                auction,
+                bids,
+                bids_total,
+                allowlist_root,
+                members,
+                proposals,
+                next_proposal_id,
+                votes,
+                vote_weights,
+                governance_token,
+                governance_config,
+                other_contract,
+                flip_count,
+                delegate_gas,
+                call_stats,
+                approved_code_hashes,
+                received_count,
+                selectors,
+                selector_names,
+                flips_by,
+                attributed_flips,
+                flippers,
+                leaderboard,
+                current_streak,
+                longest_streak,
+                badge_contract,
+                badge_milestones,
+                badges,
+                referral_terms,
+                referral_rewards,
+                referral_rewards_total,
+                history,
+                history_len,
+                expiry,
+                epoch_config,
+                value_epoch,
+                epoch_stats,
+                epoch_flips,
+                scores,
+                leaderboard_index,
+                confirmation_terms,
+                pending_confirmation,
+                next_confirmation_id,
+                confirmed_by,
+                guardian,
+                last_flip,
+                frozen,
+                account_values,
+                imported_flip_count,
+                migrated_from,
+                config,
+                delegate_layout,
+                entered,
+                delegation_disabled,
+                upgrade_signers,
+                upgrade_nonce,
+                key_rotations,
+                revoked_proposals,
+                audit_seq,
+                idempotency_keys,
+                idempotency_ring,
+                idempotency_len,
+                locked_code_hashes,
+            } = self;
+            vec![
+                delegate_to.key(),
+                owner.key(),
+                xcm_config.key(),
+                nonces.key(),
+                fee_pool.key(),
+                session_keys.key(),
+                commitments.key(),
+                oracle.key(),
+                scheduled_flips.key(),
+                next_schedule_id.key(),
+                last_flip_at.key(),
+                block_flips.key(),
+                subscription_terms.key(),
+                subscriptions.key(),
+                auction.key(),
+                bids.key(),
+                bids_total.key(),
+                allowlist_root.key(),
+                members.key(),
+                proposals.key(),
+                next_proposal_id.key(),
+                votes.key(),
+                vote_weights.key(),
+                governance_token.key(),
+                governance_config.key(),
+                other_contract.key(),
+                flip_count.key(),
+                delegate_gas.key(),
+                call_stats.key(),
+                approved_code_hashes.key(),
+                received_count.key(),
+                selectors.key(),
+                selector_names.key(),
+                flips_by.key(),
+                attributed_flips.key(),
+                flippers.key(),
+                leaderboard.key(),
+                current_streak.key(),
+                longest_streak.key(),
+                badge_contract.key(),
+                badge_milestones.key(),
+                badges.key(),
+                referral_terms.key(),
+                referral_rewards.key(),
+                referral_rewards_total.key(),
+                history.key(),
+                history_len.key(),
+                expiry.key(),
+                epoch_config.key(),
+                value_epoch.key(),
+                epoch_stats.key(),
+                epoch_flips.key(),
+                scores.key(),
+                leaderboard_index.key(),
+                confirmation_terms.key(),
+                pending_confirmation.key(),
+                next_confirmation_id.key(),
+                confirmed_by.key(),
+                guardian.key(),
+                last_flip.key(),
+                frozen.key(),
+                account_values.key(),
+                imported_flip_count.key(),
+                migrated_from.key(),
+                config.key(),
+                delegate_layout.key(),
+                entered.key(),
+                delegation_disabled.key(),
+                upgrade_signers.key(),
+                upgrade_nonce.key(),
+                key_rotations.key(),
+                revoked_proposals.key(),
+                audit_seq.key(),
+                idempotency_keys.key(),
+                idempotency_ring.key(),
+                idempotency_len.key(),
+                locked_code_hashes.key(),
+            ]
+        }
+
+        /// Returns an overview of the contract in a single query
+        #[ink(message)]
+        pub fn contract_info(&self) -> Result<ContractInfo> {
+            Ok(ContractInfo {
+                account_id: self.env().account_id(),
+                balance: self.env().balance(),
+                code_hash: self.env().own_code_hash()?,
+                delegate_to: self.delegate_to(),
+                owner: self.owner(),
+                paused: self.paused(),
+                flip_count: self.flip_count(),
+                entered: self.is_entered(),
+            })
+        }
+
+        /// Returns the code hash of the logic contract this contract delegates
+        /// to, if any
+        #[ink(message)]
+        pub fn delegate_to(&self) -> Option<Hash> {
+            self.delegate_to.get()
+        }
+
+        /// Returns the delegate to call, unless delegation was disabled,
+        /// once its recorded layout still matches the one this contract
+        /// expects.
+        ///
+        /// Only a storage read and a hash, so every delegate call can afford
+        /// it. It catches an instance whose code was swapped behind a proxy
+        /// for one expecting another layout, until the delegate is set again.
+        fn delegate(&self) -> Result<Hash> {
+            if self.delegation_disabled() {
+                return Err(Error::DelegationDisabled);
+            }
+            let delegate = self.delegate_to().ok_or(Error::DelegateNotSet)?;
+            match self.delegate_layout.get() {
+                Some(layout) if layout != self.expected_delegate_layout() => {
+                    Err(Error::LayoutMismatch)
+                }
+                _ => Ok(delegate),
+            }
+        }
+
+        /// Returns the `layout_hash` of the storage delegates have to use: a
+        /// `FlipperRoot` in the root cell.
+        fn expected_delegate_layout(&self) -> Hash {
+            use ink::storage::traits::StorageKey;
+
+            Hash::from(FlipperRoot::layout_hash(<Self as StorageKey>::KEY))
+        }
+
+        /// Returns the `layout_hash` the delegate reported when it was set,
+        /// if it was set through `set_delegate_to` or governance
+        #[ink(message)]
+        pub fn delegate_layout(&self) -> Option<Hash> {
+            self.delegate_layout.get()
+        }
+
+        /// Delegates to the logic contract with `code_hash` from now on.
+        ///
+        /// The code hash has to be approved and can't be this contract's own,
+        /// and its `layout_hash` has to match the storage this contract
+        /// keeps for the delegate. It is locked as a dependency, while the
+        /// old one stays locked until `cleanup_dependencies` unlocks it. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn set_delegate_to(&mut self, code_hash: Hash) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_delegate_to"))?;
+            self.update_delegate_to(code_hash)
+        }
+
+        /// Returns whether delegation was disabled for good
+        #[ink(message)]
+        pub fn delegation_disabled(&self) -> bool {
+            self.delegation_disabled.get().unwrap_or_default()
+        }
+
+        /// Disables delegation for good, e.g. to wind down the proxy.
+        ///
+        /// Unlike pausing, this can't be undone and stops every delegate
+        /// call, including `call_delegate_flip` and setting a new delegate,
+        /// while the contract's own messages keep working. The delegate's
+        /// code is unlocked as a dependency. Only callable by the owner.
+        #[ink(message)]
+        pub fn disable_delegation_forever(&mut self) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("disable_delegation_forever"))?;
+            if self.delegation_disabled() {
+                return Ok(());
+            }
+            let last = self.delegate_to();
+            if let Some(last) = last {
+                self.unlock_dependency(last);
+            }
+            self.delegation_disabled.set(&true);
+            self.env().emit_event(DelegationDisabled { last });
+            Ok(())
+        }
+
+        /// Returns the code hashes locked as delegate dependencies, which
+        /// can't be removed from the chain while they are locked
+        #[ink(message)]
+        pub fn locked_code_hashes(&self) -> Vec<Hash> {
+            self.locked_code_hashes.get().unwrap_or_default()
+        }
+
+        /// Unlocks the locked code hashes that are neither the delegate nor
+        /// approved, so their owners can remove the code from the chain and
+        /// reclaim its deposit. Returns how many were unlocked.
+        ///
+        /// Replaced delegates stay locked until this runs, and the runtime
+        /// limits how many dependencies a contract can lock at once. Once
+        /// delegation is disabled, everything is unlocked. Only callable by
+        /// the owner.
+        #[ink(message)]
+        pub fn cleanup_dependencies(&mut self) -> Result<u32> {
+            self.ensure_owner_and_log(ink::selector_bytes!("cleanup_dependencies"))?;
+            let unreferenced = self.unreferenced_dependencies();
+            for code_hash in &unreferenced {
+                self.unlock_dependency(*code_hash);
+            }
+            Ok(unreferenced.len() as u32)
+        }
+
+        /// Returns the locked code hashes nothing refers to anymore.
+        fn unreferenced_dependencies(&self) -> Vec<Hash> {
+            let disabled = self.delegation_disabled();
+            let delegate = self.delegate_to();
+            self.locked_code_hashes()
+                .into_iter()
+                .filter(|code_hash| {
+                    disabled
+                        || (Some(*code_hash) != delegate && !self.is_approved_code_hash(*code_hash))
+                })
+                .collect()
+        }
+
+        /// Locks `code_hash` as a dependency, so its code can't be removed
+        /// while this contract may delegate to it.
+        fn lock_dependency(&mut self, code_hash: Hash) {
+            let mut locked = self.locked_code_hashes();
+            if locked.contains(&code_hash) {
+                return;
+            }
+            self.env().lock_delegate_dependency(&code_hash);
+            locked.push(code_hash);
+            self.locked_code_hashes.set(&locked);
+        }
+
+        fn unlock_dependency(&mut self, code_hash: Hash) {
+            let mut locked = self.locked_code_hashes();
+            let Some(index) = locked.iter().position(|locked| *locked == code_hash) else {
+                return;
+            };
+            self.env().unlock_delegate_dependency(&code_hash);
+            locked.remove(index);
+            self.locked_code_hashes.set(&locked);
+            self.env().emit_event(DependencyUnlocked { code_hash });
+        }
+
+        /// Returns whether the contract may delegate to `code_hash`
+        #[ink(message)]
+        pub fn is_approved_code_hash(&self, code_hash: Hash) -> bool {
+            self.approved_code_hashes.contains(code_hash)
+        }
+
+        /// Approves the audited logic contract with `code_hash` as a delegate.
+        ///
+        /// The code hash passed to the constructor is approved from the start.
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn approve_code_hash(&mut self, code_hash: Hash) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("approve_code_hash"))?;
+            self.approved_code_hashes.insert(code_hash, &());
+            self.env().emit_event(CodeHashApproval {
+                code_hash,
+                approved: true,
+            });
+            Ok(())
+        }
+
+        /// Removes `code_hash` from the approved logic contracts.
+        ///
+        /// The current delegate keeps running until it is replaced. Only
+        /// callable by the owner.
+        #[ink(message)]
+        pub fn revoke_code_hash(&mut self, code_hash: Hash) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("revoke_code_hash"))?;
+            self.approved_code_hashes.remove(code_hash);
+            self.env().emit_event(CodeHashApproval {
+                code_hash,
+                approved: false,
+            });
+            Ok(())
+        }
+
+        /// Flips through a chain of delegate calls: each of the `relays`
+        /// delegates to the next one, and the last one to the current
+        /// delegate.
+        ///
+        /// Every contract in the chain runs against this contract's storage,
+        /// where the relays keep their depth at `flipper_storage::DEPTH_KEY`.
+        /// The relays have to be approved code hashes. Only callable by the
+        /// owner.
+        #[ink(message)]
+        pub fn chained_flip(&mut self, relays: Vec<Hash>) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("chained_flip"))?;
+            if relays.len() > relay_contract::MAX_DEPTH as usize {
+                return Err(Error::DelegateDepthExceeded);
+            }
+            if relays
+                .iter()
+                .any(|relay| !self.is_approved_code_hash(*relay))
+            {
+                return Err(Error::CodeHashNotApproved);
+            }
+            let Some((first, rest)) = relays.split_first() else {
+                return self.delegate_flip().map(drop);
+            };
+            self.settle_value();
+            let before = self.root.value;
+            let mut hops = rest.to_vec();
+            hops.push(self.delegate()?);
+
+            let selector = ink::selector_bytes!("relay");
+            let result = build_call::<Environment>()
+                .delegate(*first)
+                .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(&hops))
+                .returns::<core::result::Result<(), relay_contract::Error>>()
+                .try_invoke();
+            self.record_call(selector, matches!(result, Ok(Ok(Ok(_)))));
+            trace!("chained_flip: relays={:?} -> {:?}", relays, result);
+            match result {
+                Ok(Ok(Ok(()))) => {}
+                Ok(Ok(Err(relay_contract::Error::DepthExceeded))) => {
+                    return Err(Error::DelegateDepthExceeded)
+                }
+                _ => return Err(Error::DelegateCallFailed),
+            }
+            self.count_flip();
+            self.reload_value();
+            self.arm_expiry(before, self.root.value, self.flip_ttl());
+            Ok(())
+        }
+
+        /// Returns the number of relays currently running in a delegation
+        /// chain, which is 0 unless called from within one
+        #[ink(message)]
+        pub fn delegate_depth(&self) -> u32 {
+            ink::env::get_contract_storage::<_, u32>(&flipper_storage::DEPTH_KEY)
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        }
+
+        fn update_delegate_to(&mut self, code_hash: Hash) -> Result<()> {
+            if self.delegation_disabled() {
+                return Err(Error::DelegationDisabled);
+            }
+            if !self.is_approved_code_hash(code_hash) {
+                return Err(Error::CodeHashNotApproved);
+            }
+            // Running our own code as the delegate would only recurse into
+            // this contract's messages.
+            if Some(code_hash) == self.env().own_code_hash().ok() {
+                return Err(Error::SelfDelegation);
+            }
+            let layout = build_call::<Environment>()
+                .delegate(code_hash)
+                .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                    "layout_hash"
+                ))))
+                .returns::<Hash>()
+                .try_invoke()
+                .map_err(|_| Error::LayoutUnknown)?
+                .map_err(|_| Error::LayoutUnknown)?;
+            if layout != self.expected_delegate_layout() {
+                return Err(Error::LayoutMismatch);
+            }
+            let old = self.delegate_to();
+            self.lock_dependency(code_hash);
+            self.delegate_to.set(&code_hash);
+            self.delegate_layout.set(&layout);
+            self.env().emit_event(DelegateUpdated {
+                old,
+                new: code_hash,
+            });
+            Ok(())
+        }
+
+        /// Returns the current value, which is the one the latest flip
+        /// reverted to if it expired, or the reset value in a new epoch
+        #[ink(message)]
+        pub fn get(&self) -> bool {
+            if let Some(value) = self.epoch_reset() {
+                return value;
+            }
+            match self.expiry.get().flatten() {
+                Some(expiry) if self.env().block_number() >= expiry.at => expiry.reverts_to,
+                _ => self.root.value,
+            }
+        }
+
+        /// Returns the owner of this contract, or `None` if an instance
+        /// behind a proxy wasn't initialized yet
+        #[ink(message)]
+        pub fn owner(&self) -> Option<AccountId> {
+            self.owner.get()
+        }
+
+        /// Returns the BLAKE2-256 hash of the SCALE encoded `KeyRotation`
+        /// from the current owner to `new_key`, which the owner signs for
+        /// `rotate_admin`
+        #[ink(message)]
+        pub fn key_rotation_hash(&self, new_key: AccountId) -> Result<Hash> {
+            let rotation = KeyRotation {
+                contract: self.env().account_id(),
+                old: self.owner().ok_or(Error::NotOwner)?,
+                new: new_key,
+                nonce: self.key_rotations.get().unwrap_or_default(),
+            };
+            Ok(Hash::from(
+                self.env().hash_encoded::<Blake2x256, _>(&rotation),
+            ))
+        }
+
+        /// Makes `new_key` the owner, given the current owner's sr25519
+        /// signature `old_proof` over the `key_rotation_hash`.
+        ///
+        /// Only callable by `new_key`, so the contract can't be handed to a
+        /// key nobody holds. The proposals the old key made so far are
+        /// revoked and its scheduled flips dropped, with their deposits paid
+        /// back, so nothing it set in motion runs after the rotation.
+        #[ink(message)]
+        pub fn rotate_admin(&mut self, old_proof: [u8; 64], new_key: AccountId) -> Result<()> {
+            if self.env().caller() != new_key {
+                return Err(Error::NotNewAdmin);
+            }
+            let old = self.owner().ok_or(Error::NotOwner)?;
+            let rotation_hash = self.key_rotation_hash(new_key)?;
+            self.env()
+                .sr25519_verify(&old_proof, rotation_hash.as_ref(), old.as_ref())
+                .map_err(|_| Error::InvalidSignature)?;
+            self.log_admin(ink::selector_bytes!("rotate_admin"));
+
+            let rotations = self.key_rotations.get().unwrap_or_default();
+            self.key_rotations
+                .set(&rotations.checked_add(1).ok_or(Error::Overflow)?);
+            self.owner.set(&new_key);
+            let next_proposal_id = self.next_proposal_id.get().unwrap_or_default();
+            if next_proposal_id > 0 {
+                self.revoked_proposals.insert(old, &next_proposal_id);
+            }
+            let (dropped, scheduled): (Vec<_>, Vec<_>) = self
+                .scheduled_flips()
+                .into_iter()
+                .partition(|flip| flip.by == old);
+            let dropped_flips = dropped.len() as u32;
+            if dropped_flips > 0 {
+                self.scheduled_flips.set(&scheduled);
+            }
+            for flip in &dropped {
+                self.refund_deposit(flip)?;
+            }
+            self.env().emit_event(KeyRotated {
+                old,
+                new: new_key,
+                dropped_flips,
+            });
+            Ok(())
+        }
+
+        /// Returns whether proposal `id` by `proposer` was revoked by a key
+        /// rotation.
+        fn is_revoked(&self, id: u32, proposer: AccountId) -> bool {
+            self.revoked_proposals
+                .get(proposer)
+                .is_some_and(|before| id < before)
+        }
+
+        /// Transfers `value` from the contract to `receiver` by dispatching
+        /// `Balances::transfer_keep_alive` through `call_runtime`.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn transfer_through_runtime(
+            &mut self,
+            receiver: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("transfer_through_runtime"))?;
+            self.ensure_transferable(value)?;
+            let result =
+                self.env()
+                    .call_runtime(&RuntimeCall::Balances(BalancesCall::TransferKeepAlive {
+                        dest: MultiAddress::Id(receiver),
+                        value,
+                    }));
+            trace!(
+                "transfer_through_runtime: receiver={:?} value={} -> {:?}",
+                receiver,
+                value,
+                result
+            );
+            result.map_err(Into::into)
+        }
+
+        /// Transfers `amount` of the contract's own funds to `to`, e.g. to
+        /// rescue tokens sent to the contract by accident.
+        ///
+        /// The fee pool stays reserved for relayers, unclaimed referral
+        /// rewards for their referrers, escrowed bids for their bidders and
+        /// the deposits of scheduled flips for keepers, so only the balance
+        /// on top of them can be transferred. Only callable by the owner.
+        #[ink(message)]
+        pub fn transfer_out(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("transfer_out"))?;
+            let free = self.env().balance().saturating_sub(self.reserved_balance());
+            if free < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            self.ensure_payable(amount)?;
+            self.env().transfer(to, amount).map_err(Into::into)
+        }
+
+        /// The part of the balance owed to others: the fee pool, unclaimed
+        /// referral rewards, escrowed bids and the deposits of scheduled
+        /// flips.
+        fn reserved_balance(&self) -> Balance {
+            self.scheduled_flips()
+                .iter()
+                .fold(self.fee_pool(), |reserved, flip| {
+                    reserved.saturating_add(flip.deposit)
+                })
+                .saturating_add(self.referral_rewards_total.get().unwrap_or_default())
+                .saturating_add(self.bids_total.get().unwrap_or_default())
+        }
+
+        /// Accepts a plain value transfer and records it.
+        ///
+        /// ink! doesn't allow a wildcard selector next to other messages, so
+        /// senders have to call this message. Transfers made directly through
+        /// `pallet-balances` don't run the contract and aren't recorded.
+        #[ink(message, payable)]
+        pub fn receive(&mut self) {
+            self.received_count
+                .set(&self.received_count().saturating_add(1));
+            self.env().emit_event(Received {
+                from: self.env().caller(),
+                amount: self.env().transferred_value(),
+            });
+        }
+
+        /// Returns the number of transfers received through `receive`
+        #[ink(message)]
+        pub fn received_count(&self) -> Counter {
+            self.received_count.get().unwrap_or_default()
+        }
+
+        /// Returns the XCM configuration used by `xcm_flip`, if any
+        #[ink(message)]
+        pub fn xcm_config(&self) -> Option<XcmConfig> {
+            self.xcm_config.get()
+        }
+
+        /// Sets the XCM configuration used by `xcm_flip`.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_xcm_config(&mut self, config: XcmConfig) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_xcm_config"))?;
+            self.xcm_config.set(&config);
+            Ok(())
+        }
+
+        /// Flips the flipper at `target` on the sibling parachain `para_id`.
+        ///
+        /// Sends an XCM program that `Transact`s a `Contracts::call` into the
+        /// remote `flip` message, dispatched from this contract's sovereign
+        /// account on the destination. Returns the hash of the sent message.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn xcm_flip(&mut self, para_id: u32, target: AccountId) -> Result<[u8; 32]> {
+            self.ensure_owner_and_log(ink::selector_bytes!("xcm_flip"))?;
+            self.ensure_message_enabled(MessageFlag::Xcm)?;
+            let config = self.xcm_config.get().ok_or(Error::XcmConfigNotSet)?;
+            self.send_remote_flip(&config, para_id, target)
+        }
+
+        /// Tops up this contract's sovereign account on `para_id` with `amount`
+        /// of the destination's native asset, then flips `target` there.
+        ///
+        /// The asset is a derivative held by this contract locally; it is
+        /// released on the destination (its reserve) via `xcm_execute`, so the
+        /// subsequent `xcm_flip` program can pay for its own execution. If the
+        /// local execution fails, nothing is sent.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn xcm_fund_and_flip(
+            &mut self,
+            para_id: u32,
+            target: AccountId,
+            amount: u128,
+        ) -> Result<[u8; 32]> {
+            self.ensure_owner_and_log(ink::selector_bytes!("xcm_fund_and_flip"))?;
+            self.ensure_message_enabled(MessageFlag::Xcm)?;
+            let config = self.xcm_config.get().ok_or(Error::XcmConfigNotSet)?;
+            let reserve = Location::new(1, [Parachain(para_id)]);
+            let sovereign = Location::new(
+                1,
+                [
+                    Parachain(config.self_para_id),
+                    AccountId32 {
+                        network: None,
+                        id: *self.env().account_id().as_ref(),
+                    },
+                ],
+            );
+            let asset: Asset = (reserve.clone(), amount).into();
+            let fund: Xcm<()> = Xcm(vec![
+                WithdrawAsset(asset.into()),
+                InitiateReserveWithdraw {
+                    assets: Wild(AllCounted(1)),
+                    reserve,
+                    xcm: Xcm(vec![
+                        BuyExecution {
+                            fees: (Here, amount).into(),
+                            weight_limit: Unlimited,
+                        },
+                        DepositAsset {
+                            assets: Wild(AllCounted(1)),
+                            beneficiary: sovereign,
+                        },
+                    ]),
+                },
+            ]);
+            let result = self.env().xcm_execute(&VersionedXcm::V4(fund));
+            trace!(
+                "xcm_fund_and_flip: para_id={} amount={} -> {:?}",
+                para_id,
+                amount,
+                result
+            );
+            result?;
+
+            self.send_remote_flip(&config, para_id, target)
+        }
+
+        fn send_remote_flip(
+            &self,
+            config: &XcmConfig,
+            para_id: u32,
+            target: AccountId,
+        ) -> Result<[u8; 32]> {
+            let dest = Location::new(1, [Parachain(para_id)]);
+            let message = Self::remote_flip_message(config, target);
+
+            let result = self
+                .env()
+                .xcm_send(&VersionedLocation::V4(dest), &VersionedXcm::V4(message));
+            trace!(
+                "send_remote_flip: para_id={} target={:?} -> {:?}",
+                para_id,
+                target,
+                result
+            );
+            result.map_err(Into::into)
+        }
+
+        /// Builds the XCM program executed on the destination chain.
+        fn remote_flip_message(config: &XcmConfig, target: AccountId) -> Xcm<()> {
+            let call = (
+                config.contracts_pallet_index,
+                config.call_index,
+                MultiAddress::Id(target),
+                Compact(0u128),
+                Weight::from(config.gas_limit),
+                Option::<Compact<u128>>::None,
+                ink::selector_bytes!("flip").to_vec(),
+            )
+                .encode();
+            let transact = Transact {
+                origin_kind: OriginKind::SovereignAccount,
+                require_weight_at_most: config.transact_weight.into(),
+                call: call.into(),
+            };
+
+            if config.fee == 0 {
+                return Xcm(vec![
+                    UnpaidExecution {
+                        weight_limit: Unlimited,
+                        check_origin: None,
+                    },
+                    transact,
+                ]);
+            }
+
+            let fee: Asset = (Here, config.fee).into();
+            Xcm(vec![
+                WithdrawAsset(fee.clone().into()),
+                BuyExecution {
+                    fees: fee,
+                    weight_limit: Unlimited,
+                },
+                transact,
+            ])
+        }
+    }
+
+    /// Governance by a set of members: proposals, votes and confirmed flips.
+    ///
+    /// Only part of builds with the `governance` feature.
+    impl CrossContractFlipper {
+        /// Returns the governance members
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn members(&self) -> Vec<AccountId> {
+            self.members.get().unwrap_or_default()
+        }
+
+        /// Returns whether `account` is a governance member
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn is_member(&self, account: AccountId) -> bool {
+            self.members().contains(&account)
+        }
+
+        /// Returns the number of governance members
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn member_count(&self) -> u32 {
+            self.members().len() as u32
+        }
+
+        /// Registers `account` as a governance member.
+        ///
+        /// Only callable by the owner.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn add_member(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("add_member"))?;
+            self.insert_member(account)
+        }
+
+        /// Removes `account` from the governance members.
+        ///
+        /// It keeps its vote on proposals created before. Only callable by
+        /// the owner.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn remove_member(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("remove_member"))?;
+            let mut members = self.members();
+            members.retain(|member| *member != account);
+            self.members.set(&members);
+            Ok(())
+        }
+
+        /// Returns the PSP22 token weighting governance votes, if any
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn governance_token(&self) -> Option<AccountId> {
+            self.governance_token.get()
+        }
+
+        /// Weights the votes on proposals created from now on by the members'
+        /// balances of the PSP22 `token`.
+        ///
+        /// Only callable by the owner.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn set_governance_token(&mut self, token: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_governance_token"))?;
+            self.ensure_contract(token, None)?;
+            self.governance_token.set(&token);
+            Ok(())
+        }
+
+        /// Puts a batch of `actions` to a vote of the members.
+        ///
+        /// Once passed, the actions are executed in order within a single
+        /// transaction; if one of them fails, none of them take effect.
+        ///
+        /// Every member's voting weight is snapshotted here: its balance of
+        /// the governance token if one is set, one vote otherwise. Moving
+        /// tokens around afterwards doesn't change the outcome, so they can't
+        /// be counted twice.
+        ///
+        /// Returns the id of the new proposal. Only callable by members.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn propose(&mut self, actions: Vec<GovernanceAction>) -> Result<u32> {
+            let proposer = self.env().caller();
+            if !self.is_member(proposer) {
+                return Err(Error::NotMember);
+            }
+            if actions.is_empty() || actions.len() > MAX_PROPOSAL_ACTIONS {
+                return Err(Error::InvalidProposal);
+            }
+            let id = self.next_proposal_id.get().unwrap_or_default();
+            self.next_proposal_id
+                .set(&id.checked_add(1).ok_or(Error::Overflow)?);
+
+            let token = self.governance_token.get();
+            let mut electorate = 0;
+            for member in self.members() {
+                let weight = match token {
+                    Some(token) => self.token_balance(token, member)?,
+                    None => 1,
+                };
+                self.vote_weights.insert((id, member), &weight);
+                electorate = weight.checked_add(electorate).ok_or(Error::Overflow)?;
+            }
+            let config = self.governance_config();
+            let ends_at = self
+                .env()
+                .block_number()
+                .saturating_add(config.voting_period);
+            self.proposals.insert(
+                id,
+                &Proposal {
+                    proposer,
+                    actions: actions.clone(),
+                    ends_at,
+                    electorate,
+                    quorum: electorate
+                        .saturating_mul(config.quorum_percent.into())
+                        .div_ceil(100),
+                    executable_at: ends_at.saturating_add(config.execution_delay),
+                    votes_for: 0,
+                    votes_against: 0,
+                    executed: false,
+                },
+            );
+            self.env().emit_event(ProposalCreated {
+                id,
+                proposer,
+                actions,
+            });
+            Ok(id)
+        }
+
+        /// Returns the proposal with `id`, if any
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn proposal(&self, id: u32) -> Option<Proposal> {
+            self.proposals.get(id)
+        }
+
+        /// Votes for or against proposal `id` with the weight snapshotted
+        /// when it was created.
+        ///
+        /// Only callable by those who were members at that point.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn vote(&mut self, id: u32, support: bool) -> Result<()> {
+            let voter = self.env().caller();
+            let mut proposal = self.proposals.get(id).ok_or(Error::ProposalNotFound)?;
+            if self.is_revoked(id, proposal.proposer) {
+                return Err(Error::ProposalRevoked);
+            }
+            let weight = self.vote_weights.get((id, voter)).ok_or(Error::NotMember)?;
+            if self.env().block_number() >= proposal.ends_at {
+                return Err(Error::VotingClosed);
+            }
+            if self.votes.insert((id, voter), &()).is_some() {
+                return Err(Error::AlreadyVoted);
+            }
+            // Votes add up to at most the electorate, which didn't overflow.
+            if support {
+                proposal.votes_for = proposal.votes_for.saturating_add(weight);
+            } else {
+                proposal.votes_against = proposal.votes_against.saturating_add(weight);
+            }
+            self.proposals.insert(id, &proposal);
+            self.env().emit_event(Voted {
+                id,
+                voter,
+                support,
+                weight,
+            });
+            Ok(())
+        }
+
+        /// Returns the voting weight of `account` on proposal `id`
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn vote_weight(&self, id: u32, account: AccountId) -> u128 {
+            self.vote_weights.get((id, account)).unwrap_or_default()
+        }
+
+        /// Queries `PSP22::balance_of` of `token` for `account`.
+        #[cfg(feature = "governance")]
+        fn token_balance(&mut self, token: AccountId, account: AccountId) -> Result<u128> {
+            let selector = ink::selector_bytes!("PSP22::balance_of");
+            let result = build_call::<Environment>()
+                .call(token)
+                .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(account))
+                .returns::<u128>()
+                .try_invoke();
+            self.record_call(selector, matches!(result, Ok(Ok(_))));
+            trace!(
+                "token_balance: token={:?} account={:?} -> {:?}",
+                token,
+                account,
+                result
+            );
+            result
+                .map_err(|_| Error::TokenQueryFailed)?
+                .map_err(|_| Error::TokenQueryFailed)
+        }
+
+        /// Executes proposal `id` once its voting period and execution delay
+        /// have passed.
+        ///
+        /// A proposal passes if the quorum took part in the vote and more
+        /// weight voted for than against it. Callable by anyone.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn execute_proposal(&mut self, id: u32) -> Result<()> {
+            let mut proposal = self.proposals.get(id).ok_or(Error::ProposalNotFound)?;
+            if proposal.executed {
+                return Err(Error::AlreadyExecuted);
+            }
+            if self.is_revoked(id, proposal.proposer) {
+                return Err(Error::ProposalRevoked);
+            }
+            let now = self.env().block_number();
+            if now < proposal.ends_at {
+                return Err(Error::VotingOpen);
+            }
+            if now < proposal.executable_at {
+                return Err(Error::ExecutionDelayed);
+            }
+            let turnout = proposal.votes_for.saturating_add(proposal.votes_against);
+            if turnout < proposal.quorum || proposal.votes_for <= proposal.votes_against {
+                return Err(Error::ProposalRejected);
+            }
+            proposal.executed = true;
+            self.proposals.insert(id, &proposal);
+            self.log_admin(ink::selector_bytes!("execute_proposal"));
+
+            for action in proposal.actions {
+                self.apply_governance_action(action)?;
+            }
+            self.env().emit_event(ProposalExecuted { id });
+            Ok(())
+        }
+
+        #[cfg(feature = "governance")]
+        fn apply_governance_action(&mut self, action: GovernanceAction) -> Result<()> {
+            match action {
+                GovernanceAction::SetDelegate(code_hash) => self.update_delegate_to(code_hash)?,
+                GovernanceAction::SetGovernanceConfig(config) => {
+                    config.validate()?;
+                    self.governance_config.set(&config);
+                }
+                GovernanceAction::Pause => self.update_config(|config| config.paused = true)?,
+                GovernanceAction::Unpause => self.update_config(|config| config.paused = false)?,
+                GovernanceAction::SetRelayerFee(fee) => {
+                    self.update_config(|config| config.relayer_fee = fee)?
+                }
+            }
+            Ok(())
+        }
+
+        /// Returns the governance parameters
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn governance_config(&self) -> GovernanceConfig {
+            self.governance_config.get().unwrap_or_default()
+        }
+
+        /// Replaces the governance parameters, which only affects proposals
+        /// created afterwards.
+        ///
+        /// Governance can do the same through `SetGovernanceConfig`. Only
+        /// callable by the owner.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn set_governance_config(&mut self, config: GovernanceConfig) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_governance_config"))?;
+            config.validate()?;
+            self.governance_config.set(&config);
+            Ok(())
+        }
+
+        /// Returns how many members have to confirm a flip and the number of
+        /// blocks they have to do so, or zero confirmations if flips don't
+        /// need them
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn confirmation_terms(&self) -> (u32, BlockNumber) {
+            self.confirmation_terms.get().unwrap_or_default()
+        }
+
+        /// Makes every flip wait for `threshold` distinct members to call
+        /// `confirm_flip` within `window` blocks, zero lets accounts flip
+        /// directly again. Every flip message, the delegate calls included,
+        /// fails with `ConfirmationRequired` meanwhile.
+        ///
+        /// Drops the confirmations collected so far. Only callable by the owner.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn set_confirmation_terms(
+            &mut self,
+            threshold: u32,
+            window: BlockNumber,
+        ) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_confirmation_terms"))?;
+            if threshold > 0 && (window == 0 || threshold as usize > MAX_MEMBERS) {
+                return Err(Error::InvalidConfig);
+            }
+            self.confirmation_terms.set(&(threshold, window));
+            self.pending_confirmation.set(&None);
+            Ok(())
+        }
+
+        /// Returns the flip collecting confirmations, unless it expired
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn pending_confirmation(&self) -> Option<PendingConfirmation> {
+            self.pending_confirmation
+                .get()
+                .flatten()
+                .filter(|pending| self.env().block_number() < pending.expires_at)
+        }
+
+        /// Returns whether `account` confirmed the pending flip
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn has_confirmed(&self, account: AccountId) -> bool {
+            self.pending_confirmation()
+                .is_some_and(|pending| self.confirmed_by.contains((pending.id, account)))
+        }
+
+        /// Confirms the next flip, which happens once enough members
+        /// confirmed it within the window and is attributed to the last of
+        /// them.
+        ///
+        /// The first confirmation opens the window; if it closes too early,
+        /// the next confirmation starts over. Returns whether the value was
+        /// flipped. Only callable by members.
+        #[cfg(feature = "governance")]
+        #[ink(message)]
+        pub fn confirm_flip(&mut self) -> Result<bool> {
+            let (threshold, window) = self.confirmation_terms();
+            if threshold == 0 {
+                return Err(Error::ConfirmationsDisabled);
+            }
+            let caller = self.env().caller();
+            if !self.is_member(caller) {
+                return Err(Error::NotMember);
+            }
+            if self.paused() {
+                return Err(Error::Paused);
+            }
+            let mut pending = match self.pending_confirmation() {
+                Some(pending) => pending,
+                None => self.open_confirmation(window)?,
+            };
+            if self
+                .confirmed_by
+                .insert((pending.id, caller), &())
+                .is_some()
+            {
+                return Err(Error::AlreadyConfirmed);
+            }
+            pending.confirmations = pending.confirmations.saturating_add(1);
+            self.env().emit_event(FlipConfirmed {
+                id: pending.id,
+                by: caller,
+                confirmations: pending.confirmations,
+            });
+            if pending.confirmations < threshold {
+                self.pending_confirmation.set(&Some(pending));
+                return Ok(false);
+            }
+
+            self.pending_confirmation.set(&None);
+            self.delegate_flip()?;
+            self.record_flip_by(caller);
+            self.env().emit_event(Flipped {
+                by: caller,
+                value: self.root.value,
+                memo: None,
+            });
+            Ok(true)
+        }
+
+        /// Replaces the expired pending flip, if any, with a new one.
+        #[cfg(feature = "governance")]
+        fn open_confirmation(&mut self, window: BlockNumber) -> Result<PendingConfirmation> {
+            if let Some(expired) = self.pending_confirmation.get().flatten() {
+                self.env().emit_event(ConfirmationExpired {
+                    id: expired.id,
+                    confirmations: expired.confirmations,
+                });
+            }
+            let id = self.next_confirmation_id.get().unwrap_or_default();
+            self.next_confirmation_id
+                .set(&id.checked_add(1).ok_or(Error::Overflow)?);
+            Ok(PendingConfirmation {
+                id,
+                expires_at: self.env().block_number().saturating_add(window),
+                confirmations: 0,
+            })
+        }
+    }
+
+    impl CrossContractFlipper {
+        /// Sets the other contract's value to `value` by calling its `set`
+        /// message with the value as argument.
+        ///
+        /// Anyone may call this, since the other contract's `set` is open to
+        /// everyone anyway. `limits` overrides the configured `call_limits`.
+        #[ink(message)]
+        pub fn call_other_set(&mut self, value: bool, limits: Option<WeightLimits>) -> Result<()> {
+            let other = self
+                .other_contract
+                .get()
+                .ok_or(Error::OtherContractNotSet)?;
+            let selector = ink::selector_bytes!("set");
+            let limits = self.call_limits(limits);
+            let result = build_call::<Environment>()
+                .call(other)
+                .transferred_value(0)
+                .ref_time_limit(limits.ref_time)
+                .proof_size_limit(limits.proof_size)
+                .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(value))
+                .returns::<()>()
+                .try_invoke();
+            self.record_call(selector, matches!(result, Ok(Ok(_))));
+            trace!(
+                "call_other_set: other={:?} value={} -> {:?}",
+                other,
+                value,
+                result
+            );
+            result
+                .map_err(|_| Error::OtherCallFailed)?
+                .map_err(|_| Error::OtherCallFailed)
+        }
+
+        /// Executes `calls` in order and returns their encoded results.
+        ///
+        /// The calls are made by this contract, without value and without
+        /// allowing reentry. Their inputs can be up to `max_input_len` bytes
+        /// long. If any of them fails, reverts or returns more than
+        /// `MAX_RETURN_DATA_LEN` bytes, the whole batch is reverted. Only
+        /// callable by the owner, since the callees see this contract as the
+        /// caller.
+        #[ink(message)]
+        pub fn multicall(&mut self, calls: Vec<EncodedCall>) -> Result<Vec<Vec<u8>>> {
+            self.ensure_owner_and_log(ink::selector_bytes!("multicall"))?;
+            self.ensure_message_enabled(MessageFlag::Multicall)?;
+            calls
+                .iter()
+                .enumerate()
+                .map(|(index, call)| self.call_encoded(index, call))
+                .collect()
+        }
+
+        /// Executes `calls` in order like `multicall`, but carries on past
+        /// failed calls and returns the result of each one.
+        ///
+        /// A failed call, a reverted one included, only reverts its own
+        /// changes and emits `CallFailed`.
+        /// Only callable by the owner. Not named `try_multicall`, since ink!
+        /// already generates that name for `multicall` on the contract
+        /// reference.
+        #[ink(message)]
+        pub fn multicall_best_effort(
+            &mut self,
+            calls: Vec<EncodedCall>,
+        ) -> Result<Vec<Result<Vec<u8>>>> {
+            self.ensure_owner_and_log(ink::selector_bytes!("multicall_best_effort"))?;
+            self.ensure_message_enabled(MessageFlag::Multicall)?;
+            let mut results = Vec::with_capacity(calls.len());
+            for (index, call) in calls.iter().enumerate() {
+                let result = self.call_encoded(index, call);
+                if let Err(error) = &result {
+                    self.env().emit_event(CallFailed {
+                        index: index as u32,
+                        code: error.code(),
+                    });
+                }
+                results.push(result);
+            }
+            Ok(results)
+        }
+
+        /// Calls the message registered as `name` on `target` with the SCALE
+        /// encoded `input` and returns the encoded result.
+        ///
+        /// Works like a single call of `multicall`, `limits` overrides the
+        /// configured `call_limits`. Only callable by the owner.
+        #[ink(message)]
+        pub fn call_named(
+            &mut self,
+            target: AccountId,
+            name: String,
+            input: Vec<u8>,
+            expected_code_hash: Option<Hash>,
+            limits: Option<WeightLimits>,
+        ) -> Result<Vec<u8>> {
+            self.ensure_owner_and_log(ink::selector_bytes!("call_named"))?;
+            self.ensure_message_enabled(MessageFlag::CallNamed)?;
+            let selector = self
+                .registered_selector(name)
+                .ok_or(Error::SelectorNotRegistered)?;
+            self.call_encoded(
+                0,
+                &EncodedCall {
+                    target,
+                    selector,
+                    input,
+                    expected_code_hash,
+                    limits,
+                },
+            )
+        }
+
+        /// Returns the selector ink! derives for the message `signature`, e.g.
+        /// `flip` or `Trait::message`
+        ///
+        /// Computed on chain with BLAKE2b-256 like `ink::selector_bytes!`,
+        /// so integrators can check their own derivation against it.
+        #[ink(message)]
+        pub fn selector_of(&self, signature: String) -> [u8; 4] {
+            let mut output = [0u8; 32];
+            ink::env::hash_bytes::<Blake2x256>(signature.as_bytes(), &mut output);
+            [output[0], output[1], output[2], output[3]]
+        }
+
+        /// Returns the selector registered as `name`, if any
+        #[ink(message)]
+        pub fn registered_selector(&self, name: String) -> Option<[u8; 4]> {
+            self.selectors.get(name)
+        }
+
+        /// Returns the names in the selector registry
+        #[ink(message)]
+        pub fn selector_names(&self) -> Vec<String> {
+            self.selector_names.get().unwrap_or_default()
+        }
+
+        /// Registers `selector` as `name` for `call_named`, replacing the
+        /// selector registered as `name` before.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn register_selector(&mut self, name: String, selector: [u8; 4]) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("register_selector"))?;
+            let mut names = self.selector_names();
+            if !names.contains(&name) {
+                if names.len() >= MAX_SELECTORS {
+                    return Err(Error::TooManySelectors);
+                }
+                names.push(name.clone());
+                self.selector_names.set(&names);
+            }
+            self.selectors.insert(name, &selector);
+            Ok(())
+        }
+
+        /// Removes `name` from the selector registry.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn unregister_selector(&mut self, name: String) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("unregister_selector"))?;
+            let mut names = self.selector_names();
+            names.retain(|registered| *registered != name);
+            self.selector_names.set(&names);
+            self.selectors.remove(name);
+            Ok(())
+        }
+
+        /// Returns whether a call of `multicall` or `call_named` is running
+        ///
+        /// Always `false` between transactions, since a trapped call reverts
+        /// the lock with everything else; if it isn't, a bug left it set and
+        /// `force_clear_lock` releases it.
+        #[ink(message)]
+        pub fn is_entered(&self) -> bool {
+            self.entered.get().unwrap_or_default()
+        }
+
+        /// Releases the lock `multicall` and `call_named` hold while they
+        /// call out, in case it got stuck.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn force_clear_lock(&mut self) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("force_clear_lock"))?;
+            if self.is_entered() {
+                self.entered.set(&false);
+            }
+            Ok(())
+        }
+
+        /// Makes `call` while holding the lock, so the callee can't enter
+        /// the generic forwarder again even where the runtime would let it.
+        fn call_encoded(&mut self, index: usize, call: &EncodedCall) -> Result<Vec<u8>> {
+            if self.is_entered() {
+                return Err(Error::Reentered);
+            }
+            if call.input.len() > self.get_config().max_input_len as usize {
+                return Err(Error::InputTooLarge {
+                    index: index as u32,
+                });
+            }
+            self.ensure_contract(call.target, call.expected_code_hash)?;
+            self.entered.set(&true);
+            let result = self.invoke_encoded(index, call);
+            self.entered.set(&false);
+            result
+        }
+
+        fn invoke_encoded(&mut self, index: usize, call: &EncodedCall) -> Result<Vec<u8>> {
+            let failed = || Error::MulticallFailed {
+                index: index as u32,
+            };
+            let limits = self.call_limits(call.limits);
+            #[cfg(target_arch = "wasm32")]
+            let result = invoke_reverting(call, limits);
+            #[cfg(not(target_arch = "wasm32"))]
+            let result = build_call::<Environment>()
+                .call(call.target)
+                .transferred_value(0)
+                .ref_time_limit(limits.ref_time)
+                .proof_size_limit(limits.proof_size)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(call.selector))
+                        .push_arg(RawInput(&call.input)),
+                )
+                .returns::<RawOutput>()
+                .try_invoke();
+            self.record_call(call.selector, matches!(result, Ok(Ok(_))));
+            trace!(
+                "call_encoded: index={} call={:?} -> {:?}",
+                index,
+                call,
+                result
+            );
+            let RawOutput(output) = result
+                .map_err(|error| match error {
+                    // The length is always known for return data, so the only
+                    // way `RawOutput` fails to decode is oversized data.
+                    EnvError::Decode(_) => Error::ReturnDataTooLarge {
+                        index: index as u32,
+                    },
+                    _ => failed(),
+                })?
+                .map_err(|_| failed())?;
+            Ok(output)
+        }
+
+        /// Returns `limits`, or the configured `call_limits` if there are
+        /// none.
+        fn call_limits(&self, limits: Option<WeightLimits>) -> WeightLimits {
+            limits.or(self.get_config().call_limits).unwrap_or_default()
+        }
+
+        /// Returns the outcomes of the outbound cross-contract calls made
+        /// with `selector`
+        ///
+        /// A failure is only counted if the message that made the call still
+        /// succeeded, e.g. in `multicall_best_effort`: when the message fails,
+        /// its counter update is reverted with everything else. The queries of
+        /// the other contract aren't counted.
+        #[ink(message)]
+        pub fn call_stats(&self, selector: [u8; 4]) -> CallStats {
+            self.call_stats.get(selector).unwrap_or_default()
+        }
+
+        fn record_call(&mut self, selector: [u8; 4], success: bool) {
+            let mut stats = self.call_stats(selector);
+            if success {
+                stats.successes = stats.successes.saturating_add(1);
+            } else {
+                stats.failures = stats.failures.saturating_add(1);
+            }
+            self.call_stats.insert(selector, &stats);
+            self.audit(AuditKind::CrossCall, (selector, success).encode());
+        }
+
+        /// Checks that the contract can send `amount` without trapping:
+        /// it has to hold it, and the amount has to be able to endow a new
+        /// recipient.
+        ///
+        /// The contract's own existential deposit is already excluded from
+        /// the balance `pallet-contracts` reports, so paying out all of it
+        /// doesn't reap the contract.
+        fn ensure_transferable(&self, amount: Balance) -> Result<()> {
+            self.ensure_balance(amount)?;
+            self.ensure_payable(amount)
+        }
+
+        fn ensure_balance(&self, amount: Balance) -> Result<()> {
+            if self.env().balance() < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            Ok(())
+        }
+
+        /// Checks that `amount` is either zero or at least the existential
+        /// deposit.
+        fn ensure_payable(&self, amount: Balance) -> Result<()> {
+            if amount != 0 && amount < self.env().minimum_balance() {
+                return Err(Error::BelowMinimumBalance);
+            }
+            Ok(())
+        }
+
+        /// Checks that `account` is a contract, running `expected_code_hash`
+        /// if given, before calling it.
+        fn ensure_contract(
+            &self,
+            account: AccountId,
+            expected_code_hash: Option<Hash>,
+        ) -> Result<()> {
+            if !self.env().is_contract(&account) {
+                return Err(Error::NotAContract);
+            }
+            if let Some(expected) = expected_code_hash {
+                let code_hash = self
+                    .env()
+                    .code_hash(&account)
+                    .map_err(|_| Error::NotAContract)?;
+                if code_hash != expected {
+                    return Err(Error::CodeHashMismatch);
+                }
+            }
+            Ok(())
+        }
+
+        fn ensure_owner(&self) -> Result<()> {
+            if self.owner() != Some(self.env().caller()) {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Checks that the caller is the owner and logs the administrative
+        /// message with `selector`.
+        ///
+        /// The log is reverted with everything else if the message fails.
+        fn ensure_owner_and_log(&mut self, selector: [u8; 4]) -> Result<()> {
+            self.ensure_owner()?;
+            self.log_admin(selector);
+            Ok(())
+        }
+
+        fn log_admin(&mut self, selector: [u8; 4]) {
+            let data = (self.env().caller(), selector).encode();
+            self.audit(AuditKind::Admin, data);
+        }
+
+        fn audit(&mut self, kind: AuditKind, data: Vec<u8>) {
+            let seq = self.audit_seq();
+            self.audit_seq.set(&seq.saturating_add(1));
+            self.env().emit_event(AuditLog { seq, kind, data });
+        }
+
+        /// Returns the `seq` the next `AuditLog` event will carry, which is
+        /// the number of them emitted so far
+        #[ink(message)]
+        pub fn audit_seq(&self) -> u64 {
+            self.audit_seq.get().unwrap_or_default()
+        }
+    }
+
+    /// Queries against a deployed instance of the other contract.
+    ///
+    /// These messages take `&self`, transfer no value, allow no reentry and
+    /// bound the weight the callee may use. ink! 5 has no read-only call flag
+    /// though, so nothing stops the callee from writing to its own storage,
+    /// and the deposit for that counts against the limit of the whole call.
+    impl CrossContractFlipper {
+        /// Returns the instance of the other contract queries are sent to
+        #[ink(message)]
+        pub fn other_contract(&self) -> Option<AccountId> {
+            self.other_contract.get()
+        }
+
+        /// Sets the instance of the other contract queries are sent to.
+        ///
+        /// Only callable by the owner.
+        #[ink(message)]
+        pub fn set_other_contract(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner_and_log(ink::selector_bytes!("set_other_contract"))?;
+            self.ensure_contract(account, None)?;
+            self.other_contract.set(&account);
+            Ok(())
+        }
+
+        /// Returns the value stored by the other contract.
+        ///
+        /// Its `get` is trusted not to write, since ink! 5 can't make the
+        /// call read-only.
+        #[ink(message)]
+        pub fn query_other_value(&self) -> Result<bool> {
+            self.query_other(ink::selector_bytes!("get"))
+        }
+
+        /// Returns the account id the other contract reports for itself.
+        ///
+        /// Its `get_account_id` is trusted not to write, since ink! 5 can't
+        /// make the call read-only.
+        #[ink(message)]
+        pub fn query_other_account_id(&self) -> Result<AccountId> {
+            self.query_other(ink::selector_bytes!("get_account_id"))
+        }
+
+        fn query_other<R: ink::scale::Decode>(&self, selector: [u8; 4]) -> Result<R> {
+            let other = self
+                .other_contract
+                .get()
+                .ok_or(Error::OtherContractNotSet)?;
+            self.query(other, selector)
+        }
+
+        /// Calls the argument-less message `selector` of `contract` within
+        /// `QUERY_LIMITS`, without transferring value.
+        fn query<R: ink::scale::Decode>(
+            &self,
+            contract: AccountId,
+            selector: [u8; 4],
+        ) -> Result<R> {
+            let result = build_call::<Environment>()
+                .call(contract)
+                .transferred_value(0)
+                .ref_time_limit(QUERY_LIMITS.ref_time)
+                .proof_size_limit(QUERY_LIMITS.proof_size)
+                .exec_input(ExecutionInput::new(Selector::new(selector)))
+                .returns::<R>()
+                .try_invoke();
+            trace!(
+                "query: contract={:?} selector={:?} -> ok={}",
+                contract,
+                selector,
+                matches!(result, Ok(Ok(_)))
+            );
+            result
+                .map_err(|_| Error::QueryFailed)?
+                .map_err(|_| Error::QueryFailed)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::{prelude::vec::Vec, scale::Encode};
+
+        /// Stands in for the runtime's `FetchRandom` chain extension.
+        struct MockedRandomExtension {
+            random: [u8; 32],
+            status: u32,
+        }
+
+        impl ink::env::test::ChainExtension for MockedRandomExtension {
+            fn ext_id(&self) -> u16 {
+                1
+            }
+
+            fn call(&mut self, _func_id: u16, _input: &[u8], output: &mut Vec<u8>) -> u32 {
+                self.random.encode_to(output);
+                self.status
+            }
+        }
+
+        fn flipper(value: bool) -> CrossContractFlipper {
+            let mut flipper = CrossContractFlipper {
+                root: FlipperRoot { value },
+                ..Default::default()
+            };
+            flipper.owner.set(&AccountId::from([0x01; 32]));
+            flipper
+        }
+
+        #[ink::test]
+        fn random_flip_skips_even_bytes() {
+            ink::env::test::register_chain_extension(MockedRandomExtension {
+                random: [2; 32],
+                status: 0,
+            });
+            let mut flipper = flipper(false);
+
+            assert_eq!(flipper.random_flip(), Ok(false));
+            assert!(!flipper.get());
+        }
+
+        #[ink::test]
+        fn random_flip_surfaces_extension_errors() {
+            ink::env::test::register_chain_extension(MockedRandomExtension {
+                random: [1; 32],
+                status: 1,
+            });
+            let mut flipper = flipper(false);
+
+            assert_eq!(flipper.random_flip(), Err(Error::RandomnessUnavailable));
+            assert!(!flipper.get());
+        }
+
+        #[ink::test]
+        #[cfg(feature = "meta-tx")]
+        fn session_keys_are_checked_and_expire() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+
+            assert_eq!(
+                flipper.register_session_key(accounts.bob, 0),
+                Err(Error::InvalidExpiry)
+            );
+            assert_eq!(flipper.register_session_key(accounts.bob, 2), Ok(()));
+            assert_eq!(
+                flipper.session_key_of(accounts.alice),
+                Some(SessionKey {
+                    key: accounts.bob,
+                    expires_at: 2
+                })
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.flip_as(accounts.alice), Err(Error::NotSessionKey));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.flip_as(accounts.alice), Err(Error::SessionExpired));
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            flipper.revoke_session_key();
+            assert_eq!(flipper.session_key_of(accounts.alice), None);
+        }
+
+        #[ink::test]
+        fn reveal_checks_the_commitment() {
+            let mut flipper = flipper(false);
+            let salt = [0x07; 32];
+            let mut hash = [0; 32];
+            ink::env::hash_encoded::<Blake2x256, _>(&(false, salt), &mut hash);
+
+            assert_eq!(flipper.reveal(false, salt), Err(Error::NoCommitment));
+            assert_eq!(flipper.commit(Hash::from(hash)), Ok(()));
+            assert_eq!(
+                flipper.commit(Hash::from(hash)),
+                Err(Error::CommitmentPending)
+            );
+            assert_eq!(flipper.reveal(true, salt), Err(Error::RevealMismatch));
+            // The committed value is the current one, so nothing is flipped.
+            assert_eq!(flipper.reveal(false, salt), Ok(false));
+            assert_eq!(
+                flipper.commitment_of(ink::env::caller::<Environment>()),
+                None
+            );
+        }
+
+        #[ink::test]
+        fn scheduled_flips_stay_ordered() {
+            let mut flipper = flipper(false);
+
+            assert_eq!(flipper.schedule_flip(0), Err(Error::NotInFuture));
+            assert_eq!(flipper.schedule_flip(5), Ok(0));
+            assert_eq!(flipper.schedule_flip(3), Ok(1));
+            assert_eq!(flipper.schedule_flip(5), Ok(2));
+            let ids: Vec<u64> = flipper.scheduled_flips().iter().map(|f| f.id).collect();
+            assert_eq!(ids, [1, 0, 2]);
+            assert_eq!(flipper.poke(), Err(Error::NothingDue));
+        }
+
+        #[ink::test]
+        fn failed_scheduled_flips_are_dropped() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.schedule_flip(1), Ok(0));
+            assert_eq!(flipper.schedule_flip(1), Ok(1));
+            assert_eq!(flipper.schedule_flip(2), Ok(2));
+
+            // Without a delegate every flip fails.
+            ink::env::test::advance_block::<Environment>();
+            let events = ink::env::test::recorded_events().count();
+            assert_eq!(flipper.poke(), Ok(0));
+            let failures: Vec<_> = ink::env::test::recorded_events()
+                .skip(events)
+                .map(|event| {
+                    ScheduledFlipFailed::decode(&mut &event.data[..])
+                        .expect("a ScheduledFlipFailed")
+                })
+                .map(|failed| (failed.id, failed.code))
+                .collect();
+            let code = Error::DelegateNotSet.code();
+            assert_eq!(failures, [(0, code), (1, code)]);
+            // The failed flips neither start a cooldown nor use up the
+            // block's budget.
+            let caller = ink::env::caller::<Environment>();
+            assert_eq!(flipper.last_flip_at.get(caller), None);
+            assert_eq!(flipper.flips_in_current_block(), 0);
+            let ids: Vec<u64> = flipper.scheduled_flips().iter().map(|f| f.id).collect();
+            assert_eq!(ids, [2]);
+        }
+
+        #[ink::test]
+        fn scheduled_flips_take_a_deposit() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let contract = ink::env::account_id::<Environment>();
+            let minimum = ink::env::minimum_balance::<Environment>();
+            ink::env::test::set_account_balance::<Environment>(contract, 10 * minimum);
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_keeper_bounty(minimum), Ok(()));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            ink::env::test::set_value_transferred::<Environment>(minimum - 1);
+            assert_eq!(flipper.schedule_flip(5), Err(Error::InsufficientPayment));
+            ink::env::test::set_value_transferred::<Environment>(minimum);
+            for at in 1..=MAX_SCHEDULED_FLIPS_PER_ACCOUNT as BlockNumber {
+                assert_eq!(flipper.schedule_flip(at).map(drop), Ok(()));
+            }
+            assert_eq!(flipper.schedule_flip(5), Err(Error::TooManyScheduledFlips));
+            let owed = MAX_SCHEDULED_FLIPS_PER_ACCOUNT as Balance * minimum;
+            assert_eq!(flipper.validate_migration().owed, owed);
+
+            // Without a delegate the due flip fails, so bob gets the deposit
+            // back instead of the keeper.
+            ink::env::test::advance_block::<Environment>();
+            let before = ink::env::test::get_account_balance::<Environment>(accounts.bob);
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.poke(), Ok(0));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(accounts.bob),
+                before.map(|balance| balance + minimum)
+            );
+            assert_eq!(flipper.validate_migration().owed, owed - minimum);
+        }
+
+        #[ink::test]
+        fn subscriptions_extend_by_paid_periods() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.subscribe(), Err(Error::SubscriptionsDisabled));
+            assert_eq!(
+                flipper.set_subscription_terms(10, 0),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.set_subscription_terms(10, 5), Ok(()));
+
+            ink::env::test::set_value_transferred::<Environment>(9);
+            assert_eq!(flipper.subscribe(), Err(Error::InsufficientPayment));
+            ink::env::test::set_value_transferred::<Environment>(25);
+            assert_eq!(flipper.subscribe(), Ok(10));
+            ink::env::test::set_value_transferred::<Environment>(10);
+            assert_eq!(flipper.subscribe(), Ok(15));
+            assert_eq!(flipper.fee_pool(), 35);
+        }
+
+        #[ink::test]
+        fn delegate_flips_need_a_subscription() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_subscription_terms(10, 5), Ok(()));
+            assert_eq!(
+                flipper.call_delegate_flip(),
+                Err(Error::SubscriptionRequired)
+            );
+            for mode in [DelegateMode::Decoded, DelegateMode::TailCall] {
+                assert_eq!(
+                    flipper.delegate_flip_with(mode),
+                    Err(Error::SubscriptionRequired)
+                );
+            }
+
+            // Subscribed, the flips get as far as the missing delegate.
+            ink::env::test::set_value_transferred::<Environment>(10);
+            assert_eq!(flipper.subscribe(), Ok(5));
+            assert_eq!(flipper.call_delegate_flip(), Err(Error::DelegateNotSet));
+            for mode in [DelegateMode::Decoded, DelegateMode::TailCall] {
+                assert_eq!(flipper.delegate_flip_with(mode), Err(Error::DelegateNotSet));
+            }
+        }
+
+        #[ink::test]
+        #[cfg(feature = "auctions")]
+        fn losing_bids_can_be_reclaimed_after_close() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let contract = ink::env::account_id::<Environment>();
+            ink::env::test::set_account_balance::<Environment>(contract, 1_000_000_000);
+            let mut flipper = flipper(false);
+            ink::env::test::set_caller::<Environment>(AccountId::from([0x01; 32]));
+            assert_eq!(flipper.start_auction(2), Ok(0));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            ink::env::test::set_value_transferred::<Environment>(10);
+            assert_eq!(flipper.bid(), Ok(()));
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.bid(), Err(Error::BidTooLow));
+            ink::env::test::set_value_transferred::<Environment>(11);
+            assert_eq!(flipper.bid(), Ok(()));
+            assert_eq!(flipper.claim_auction_flip(), Err(Error::AuctionStillOpen));
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.bid(), Err(Error::AuctionNotActive));
+            assert_eq!(flipper.reclaim_bid(0), Err(Error::NothingToReclaim));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.claim_auction_flip(), Err(Error::NotAuctionWinner));
+            assert_eq!(flipper.reclaim_bid(0), Ok(10));
+            assert_eq!(flipper.reclaim_bid(0), Err(Error::NothingToReclaim));
+        }
+
+        #[ink::test]
+        #[cfg(feature = "auctions")]
+        fn unclaimed_wins_are_settled_after_the_claim_period() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let contract = ink::env::account_id::<Environment>();
+            ink::env::test::set_account_balance::<Environment>(contract, 1_000_000_000);
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.start_auction(1), Ok(0));
+            for (bidder, value) in [(accounts.bob, 10), (accounts.charlie, 11)] {
+                ink::env::test::set_caller::<Environment>(bidder);
+                ink::env::test::set_value_transferred::<Environment>(value);
+                assert_eq!(flipper.bid(), Ok(()));
+            }
+
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.settle_auction(), Err(Error::ClaimPeriodOpen));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.start_auction(1), Err(Error::AuctionUnsettled));
+            for _ in 0..AUCTION_CLAIM_PERIOD {
+                ink::env::test::advance_block::<Environment>();
+            }
+            ink::env::test::set_caller::<Environment>(accounts.django);
+            assert_eq!(flipper.settle_auction(), Ok(()));
+            assert_eq!(flipper.settle_auction(), Err(Error::NothingToSettle));
+            assert_eq!(flipper.fee_pool(), 11);
+            assert_eq!(flipper.bid_of(0, accounts.charlie), 0);
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.start_auction(1), Ok(1));
+            // Losing bids of earlier auctions stay reclaimable.
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.bid_of(0, accounts.bob), 10);
+            assert_eq!(flipper.reclaim_bid(0), Ok(10));
+        }
+
+        #[ink::test]
+        fn merkle_proofs_are_verified() {
+            let hash = |input: &[u8]| {
+                let mut output = [0; 32];
+                ink::env::hash_bytes::<Blake2x256>(input, &mut output);
+                output
+            };
+            let (alice, bob, charlie) = (hash(&[1; 32]), hash(&[2; 32]), hash(&[3; 32]));
+            let pair = |a: [u8; 32], b: [u8; 32]| hash(&[a.min(b), a.max(b)].concat());
+            let root = Hash::from(pair(pair(alice, bob), charlie));
+
+            assert!(CrossContractFlipper::verify_merkle_proof(
+                root,
+                bob,
+                &[alice, charlie]
+            ));
+            assert!(CrossContractFlipper::verify_merkle_proof(
+                root,
+                charlie,
+                &[pair(alice, bob)]
+            ));
+            assert!(!CrossContractFlipper::verify_merkle_proof(
+                root,
+                charlie,
+                &[alice]
+            ));
+
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.flip_with_proof(Vec::new()),
+                Err(Error::AllowlistNotSet)
+            );
+            flipper.allowlist_root.set(&root);
+            assert_eq!(flipper.flip_with_proof(vec![bob]), Err(Error::InvalidProof));
+        }
+
+        #[ink::test]
+        #[cfg(feature = "governance")]
+        fn proposals_need_a_majority_of_members() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            let actions = vec![
+                GovernanceAction::Pause,
+                GovernanceAction::SetDelegate(Hash::from([0x09; 32])),
+            ];
+            for member in [accounts.bob, accounts.charlie, accounts.django] {
+                assert_eq!(flipper.add_member(member), Ok(()));
+            }
+            assert_eq!(
+                flipper.set_governance_config(GovernanceConfig {
+                    quorum_percent: 101,
+                    ..Default::default()
+                }),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.member_count(), 3);
+
+            ink::env::test::set_caller::<Environment>(accounts.eve);
+            assert_eq!(flipper.propose(actions.clone()), Err(Error::NotMember));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.propose(Vec::new()), Err(Error::InvalidProposal));
+            assert_eq!(flipper.propose(actions), Ok(0));
+            assert_eq!(flipper.vote_weight(0, accounts.charlie), 1);
+            assert_eq!(flipper.proposal(0).map(|p| p.electorate), Some(3));
+            assert_eq!(flipper.vote(0, true), Ok(()));
+            assert_eq!(flipper.vote(0, true), Err(Error::AlreadyVoted));
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.vote(0, false), Ok(()));
+            assert_eq!(flipper.execute_proposal(0), Err(Error::VotingOpen));
+
+            for _ in 0..GovernanceConfig::default().voting_period {
+                ink::env::test::advance_block::<Environment>();
+            }
+            assert_eq!(flipper.vote(0, true), Err(Error::VotingClosed));
+            assert_eq!(flipper.execute_proposal(0), Err(Error::ProposalRejected));
+            assert_eq!(flipper.execute_proposal(1), Err(Error::ProposalNotFound));
+        }
+
+        #[ink::test]
+        #[cfg(feature = "governance")]
+        fn flips_wait_for_confirmations() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.confirm_flip(), Err(Error::ConfirmationsDisabled));
+            assert_eq!(
+                flipper.set_confirmation_terms(2, 0),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.set_confirmation_terms(3, 2), Ok(()));
+            for member in [accounts.bob, accounts.charlie, accounts.django] {
+                assert_eq!(flipper.add_member(member), Ok(()));
+            }
+            assert_eq!(
+                flipper.flip_with_memo(Vec::new()),
+                Err(Error::ConfirmationRequired)
+            );
+            assert_eq!(flipper.confirm_flip(), Err(Error::NotMember));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.confirm_flip(), Ok(false));
+            assert_eq!(flipper.confirm_flip(), Err(Error::AlreadyConfirmed));
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.confirm_flip(), Ok(false));
+            assert!(flipper.has_confirmed(accounts.bob));
+            assert_eq!(
+                flipper.pending_confirmation(),
+                Some(PendingConfirmation {
+                    id: 0,
+                    expires_at: 2,
+                    confirmations: 2,
+                })
+            );
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.pending_confirmation(), None);
+            assert!(!flipper.has_confirmed(accounts.bob));
+            assert_eq!(flipper.confirm_flip(), Ok(false));
+            assert_eq!(flipper.pending_confirmation().map(|p| p.id), Some(1));
+            assert!(!flipper.get());
+        }
+
+        #[ink::test]
+        #[cfg(feature = "governance")]
+        fn delegate_flips_wait_for_confirmations_too() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_confirmation_terms(1, 2), Ok(()));
+            assert_eq!(
+                flipper.call_delegate_flip(),
+                Err(Error::ConfirmationRequired)
+            );
+            for mode in [DelegateMode::Decoded, DelegateMode::TailCall] {
+                assert_eq!(
+                    flipper.delegate_flip_with(mode),
+                    Err(Error::ConfirmationRequired)
+                );
+            }
+        }
+
+        #[ink::test]
+        fn delegates_have_to_be_approved() {
+            let mut flipper = flipper(false);
+            let code_hash = Hash::from([0x09; 32]);
+            assert_eq!(
+                flipper.set_delegate_to(code_hash),
+                Err(Error::CodeHashNotApproved)
+            );
+            assert_eq!(flipper.approve_code_hash(code_hash), Ok(()));
+            assert!(flipper.is_approved_code_hash(code_hash));
+            assert_eq!(flipper.revoke_code_hash(code_hash), Ok(()));
+            assert!(!flipper.is_approved_code_hash(code_hash));
+        }
+
+        #[ink::test]
+        fn delegation_chains_are_bounded() {
+            let mut flipper = flipper(false);
+            let relay = Hash::from([0x09; 32]);
+            assert_eq!(
+                flipper.chained_flip(vec![relay]),
+                Err(Error::CodeHashNotApproved)
+            );
+            assert_eq!(flipper.approve_code_hash(relay), Ok(()));
+            assert_eq!(
+                flipper.chained_flip(vec![relay; relay_contract::MAX_DEPTH as usize + 1]),
+                Err(Error::DelegateDepthExceeded)
+            );
+            assert_eq!(flipper.delegate_depth(), 0);
+        }
+
+        #[ink::test]
+        fn stats_count_flips_and_flippers() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            flipper.record_flip_by(accounts.bob);
+            flipper.record_flip_by(accounts.bob);
+            flipper.record_flip_by(accounts.charlie);
+            assert_eq!(flipper.stats_of(accounts.bob), 2);
+            assert_eq!(flipper.stats_of(accounts.django), 0);
+            assert_eq!(
+                flipper
+                    .get_many(vec![accounts.charlie, accounts.bob])
+                    .map(|states| states.iter().map(|state| state.flips).collect::<Vec<_>>()),
+                Ok(vec![1, 2])
+            );
+            assert_eq!(
+                flipper.get_many(vec![accounts.bob; MAX_BATCH_ACCOUNTS + 1]),
+                Err(Error::TooManyAccounts)
+            );
+            assert_eq!(
+                flipper.leaderboard(),
+                vec![(accounts.bob, 2), (accounts.charlie, 1)]
+            );
+            assert_eq!(
+                flipper.current_streak(),
+                Some(Streak {
+                    account: accounts.charlie,
+                    length: 1
+                })
+            );
+            assert_eq!(
+                flipper.longest_streak(),
+                Some(Streak {
+                    account: accounts.bob,
+                    length: 2
+                })
+            );
+            assert_eq!(
+                flipper.global_stats(),
+                GlobalStats {
+                    total_flips: 0,
+                    attributed_flips: 3,
+                    flippers: 2,
+                }
+            );
+
+            let page = flipper.flip_history(None, 2);
+            assert_eq!(
+                page.records
+                    .iter()
+                    .map(|record| record.by)
+                    .collect::<Vec<_>>(),
+                vec![accounts.bob, accounts.bob]
+            );
+            flipper.record_flip_by(accounts.django);
+            let page = flipper.flip_history(Some(page.next), 2);
+            assert_eq!(
+                page.records
+                    .iter()
+                    .map(|record| record.by)
+                    .collect::<Vec<_>>(),
+                vec![accounts.charlie, accounts.django]
+            );
+            assert!(flipper.flip_history(Some(page.next), 2).records.is_empty());
+        }
+
+        #[ink::test]
+        fn badge_milestones_have_to_increase() {
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.set_badge_milestones(vec![10, 10]),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(
+                flipper.set_badge_milestones(vec![0, 10]),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.set_badge_milestones(vec![1, 10, 100]), Ok(()));
+
+            // Reaching a milestone without a badge contract leaves it to claim.
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            flipper.record_flip_by(accounts.bob);
+            assert!(!flipper.has_badge(accounts.bob, 1));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.claim_badges(), Err(Error::BadgeContractNotSet));
+        }
+
+        #[ink::test]
+        fn referrers_claim_their_rewards() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let minimum = ink::env::minimum_balance::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.flip_with_referrer(accounts.bob),
+                Err(Error::ReferralsDisabled)
+            );
+            assert_eq!(
+                flipper.set_referral_terms(minimum, 101),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.set_referral_terms(minimum, 10), Ok(()));
+            assert_eq!(
+                flipper.flip_with_referrer(accounts.alice),
+                Err(Error::SelfReferral)
+            );
+            assert_eq!(
+                flipper.flip_with_referrer(accounts.bob),
+                Err(Error::InsufficientPayment)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.claim_referral_rewards(), Err(Error::NothingToClaim));
+            flipper.referral_rewards.insert(accounts.bob, &minimum);
+            flipper.referral_rewards_total.set(&minimum);
+            assert_eq!(flipper.claim_referral_rewards(), Ok(minimum));
+            assert_eq!(flipper.referral_rewards_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn batches_are_bounded() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.flip_n(0), Err(Error::InvalidFlipCount));
+            assert_eq!(
+                flipper.flip_n(MAX_BATCH_FLIPS + 1),
+                Err(Error::InvalidFlipCount)
+            );
+            assert_eq!(flipper.set_max_flips_per_block(2), Ok(()));
+            assert_eq!(flipper.flip_n(3), Err(Error::BlockFlipLimitReached));
+        }
+
+        #[ink::test]
+        fn expired_flips_revert() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_flip_ttl(2), Ok(()));
+            assert_eq!(flipper.flip_with_ttl(Some(1)), Err(Error::InvalidTtl));
+            flipper.root.value = true;
+            flipper.arm_expiry(false, true, flipper.flip_ttl());
+            assert!(flipper.get());
+            assert_eq!(flipper.expires_at(), Some(2));
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert!(!flipper.get());
+            assert_eq!(flipper.expires_at(), None);
+            flipper.settle_value();
+            assert!(!flipper.root.value);
+
+            // Flipping back to the value before cancels the expiry.
+            flipper.root.value = true;
+            flipper.arm_expiry(false, true, 2);
+            flipper.root.value = false;
+            flipper.arm_expiry(true, false, 2);
+            assert_eq!(flipper.expires_at(), None);
+        }
+
+        #[ink::test]
+        fn guardians_veto_flips_within_the_window() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.last_flip_status(), None);
+            assert_eq!(flipper.set_veto_window(2), Ok(()));
+            assert_eq!(flipper.set_guardian(accounts.bob), Ok(()));
+            flipper.root.value = true;
+            flipper.open_veto_window(false);
+            assert_eq!(
+                flipper.last_flip_status(),
+                Some(FlipStatus::Pending { final_at: 2 })
+            );
+            assert_eq!(flipper.veto_last_flip(), Err(Error::NotGuardian));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.veto_last_flip(), Ok(()));
+            assert!(!flipper.get());
+            assert_eq!(flipper.last_flip_status(), Some(FlipStatus::Vetoed));
+            assert_eq!(flipper.veto_last_flip(), Err(Error::NothingToVeto));
+
+            flipper.root.value = true;
+            flipper.open_veto_window(false);
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.last_flip_status(), Some(FlipStatus::Final));
+            assert_eq!(flipper.veto_last_flip(), Err(Error::NothingToVeto));
+            assert!(flipper.get());
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.set_veto_window(0), Ok(()));
+            flipper.open_veto_window(true);
+            assert_eq!(flipper.last_flip_status(), None);
+        }
+
+        #[ink::test]
+        fn frozen_accounts_cant_flip() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_frozen(accounts.bob, true), Ok(()));
+            assert!(flipper.is_frozen(accounts.bob));
+            assert_eq!(
+                flipper.admit_flips(accounts.bob, 1),
+                Err(Error::AccountFrozen)
+            );
+            assert_eq!(flipper.admit_flips(accounts.charlie, 1), Ok(()));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.call_delegate_flip(), Err(Error::AccountFrozen));
+            assert_eq!(
+                flipper.commit(Hash::from([0x07; 32])),
+                Err(Error::AccountFrozen)
+            );
+            #[cfg(feature = "meta-tx")]
+            assert_eq!(
+                flipper.register_session_key(accounts.eve, 10),
+                Err(Error::AccountFrozen)
+            );
+            assert_eq!(
+                flipper.set_frozen(accounts.bob, false),
+                Err(Error::NotOwner)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.set_frozen(accounts.bob, false), Ok(()));
+            assert_eq!(flipper.admit_flips(accounts.bob, 1), Ok(()));
+        }
+
+        #[ink::test]
+        fn disabled_messages_are_rejected() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.set_message_enabled(MessageFlag::Multicall, false),
+                Ok(())
+            );
+            assert_eq!(
+                flipper.set_message_enabled(MessageFlag::FlipN, false),
+                Ok(())
+            );
+            assert!(!flipper.message_enabled(MessageFlag::Multicall));
+            assert!(flipper.message_enabled(MessageFlag::CallNamed));
+            assert_eq!(
+                flipper.get_config().disabled_messages,
+                MessageFlag::Multicall.bit() | MessageFlag::FlipN.bit()
+            );
+            assert_eq!(flipper.multicall(Vec::new()), Err(Error::MessageDisabled));
+            assert_eq!(flipper.flip_n(1), Err(Error::MessageDisabled));
+            #[cfg(feature = "meta-tx")]
+            {
+                assert_eq!(
+                    flipper.set_message_enabled(MessageFlag::MetaTx, false),
+                    Ok(())
+                );
+                assert_eq!(flipper.flip_as(accounts.bob), Err(Error::MessageDisabled));
+            }
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                flipper.set_message_enabled(MessageFlag::FlipN, true),
+                Err(Error::NotOwner)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                flipper.set_message_enabled(MessageFlag::FlipN, true),
+                Ok(())
+            );
+            assert_eq!(flipper.flip_n(1), Err(Error::DelegateNotSet));
+            assert_eq!(flipper.multicall(Vec::new()), Err(Error::MessageDisabled));
+        }
+
+        #[ink::test]
+        fn owners_set_values_in_batches() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.batch_set(vec![(accounts.bob, true); MAX_BATCH_ACCOUNTS + 1]),
+                Err(Error::TooManyAccounts)
+            );
+            assert_eq!(
+                flipper.batch_set(vec![(accounts.bob, true), (accounts.charlie, true)]),
+                Ok(())
+            );
+            assert!(flipper.value_of(accounts.bob));
+            assert!(!flipper.value_of(accounts.django));
+
+            assert_eq!(flipper.set_frozen(accounts.charlie, true), Ok(()));
+            assert_eq!(
+                flipper.batch_set(vec![(accounts.bob, false), (accounts.charlie, false)]),
+                Err(Error::AccountFrozen)
+            );
+            assert!(flipper.value_of(accounts.bob));
+            assert_eq!(flipper.batch_set(vec![(accounts.bob, false)]), Ok(()));
+            assert!(!flipper.value_of(accounts.bob));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.batch_set(Vec::new()), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn state_moves_to_a_fresh_instance() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut old = flipper(true);
+            assert_eq!(old.insert_member(accounts.bob), Ok(()));
+            assert_eq!(old.register_selector("flip".into(), [0x01; 4]), Ok(()));
+            old.record_flip_by(accounts.charlie);
+            old.record_flip_by(accounts.django);
+            old.count_flip();
+
+            // Each instance gets its own account and with that its storage.
+            let old_account = ink::env::test::callee::<Environment>();
+            let new_account = AccountId::from([0x42; 32]);
+            let mut chunks = Vec::new();
+            let mut offset = 0;
+            loop {
+                let chunk = old.export_state(offset, 2).unwrap();
+                let len = Vec::<StateItem>::decode(&mut &chunk[..]).unwrap().len() as u32;
+                if len == 0 {
+                    break;
+                }
+                chunks.push(chunk);
+                offset += len;
+            }
+            assert_eq!(offset, 5);
+            let old_history = old.flip_history(None, 10);
+            let old_stats = old.global_stats();
+
+            ink::env::test::set_callee::<Environment>(new_account);
+            let mut new = flipper(false);
+            for chunk in chunks.iter().rev() {
+                assert!(new.import_state(chunk.clone()).is_ok());
+            }
+            assert!(new.get());
+            assert_eq!(new.flip_count(), 1);
+            assert_eq!(new.global_stats(), old_stats);
+            assert_eq!(new.members.get(), Some(vec![accounts.bob]));
+            assert_eq!(new.registered_selector("flip".into()), Some([0x01; 4]));
+            assert_eq!(new.flip_history(None, 10), old_history);
+            assert_eq!(new.stats_of(accounts.charlie), 1);
+            // Importing the history again counts its accounts only once,
+            // and so does a later flip of one of them.
+            for chunk in &chunks {
+                assert!(new.import_state(chunk.clone()).is_ok());
+            }
+            new.record_flip_by(accounts.charlie);
+            assert_eq!(new.stats_of(accounts.charlie), 2);
+            assert_eq!(new.global_stats().flippers, old_stats.flippers);
+
+            assert_eq!(new.import_state(vec![0x07]), Err(Error::InvalidStateChunk));
+            new.count_flip();
+            assert_eq!(
+                new.import_state(chunks[0].clone()),
+                Err(Error::ImportClosed)
+            );
+            ink::env::test::set_callee::<Environment>(old_account);
+            assert_eq!(old.flip_count(), 1);
+        }
+
+        #[ink::test]
+        fn migrations_run_once_from_a_contract() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.migrate_from(accounts.bob), Err(Error::NotAContract));
+            flipper.migrated_from.set(&accounts.bob);
+            assert_eq!(
+                flipper.migrate_from(accounts.bob),
+                Err(Error::AlreadyMigrated)
+            );
+            assert_eq!(flipper.migrated_from(), Some(accounts.bob));
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.migrate_from(accounts.bob), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn migrations_read_narrow_counters() {
+            let stats = GlobalStatsV2::decode(&mut &(7u64, 5u64, 2u64).encode()[..]).unwrap();
+            assert_eq!(
+                GlobalStats::from(stats),
+                GlobalStats {
+                    total_flips: 7,
+                    attributed_flips: 5,
+                    flippers: 2,
+                }
+            );
+
+            let owner = AccountId::from([0x01; 32]);
+            for encoded in [owner.encode(), Some(owner).encode()] {
+                let QueriedOwner(decoded) = QueriedOwner::decode(&mut &encoded[..]).unwrap();
+                assert_eq!(decoded, Some(owner));
+            }
+            let QueriedOwner(decoded) =
+                QueriedOwner::decode(&mut &Option::<AccountId>::None.encode()[..]).unwrap();
+            assert_eq!(decoded, None);
+        }
+
+        #[ink::test]
+        fn migrations_wait_for_pending_state() {
+            let mut flipper = flipper(false);
+            let report = flipper.validate_migration();
+            assert!(!report.ready);
+            assert_eq!(report.storage_version, STORAGE_VERSION);
+            assert_eq!(report.layout_hash, flipper.validate_migration().layout_hash);
+
+            assert_eq!(flipper.set_paused(true), Ok(()));
+            assert!(flipper.validate_migration().ready);
+            assert_eq!(flipper.set_veto_window(2), Ok(()));
+            flipper.open_veto_window(false);
+            let report = flipper.validate_migration();
+            assert!(report.veto_pending);
+            assert!(!report.ready);
+        }
+
+        #[ink::test]
+        fn root_cell_holds_only_the_value() {
+            let key = <CrossContractFlipper as ink::storage::traits::StorageKey>::KEY;
+            let mut flipper = flipper(true);
+            assert_eq!(flipper.set_config(Config::default()), Ok(()));
+            ink::env::set_contract_storage(&key, &flipper);
+            assert_eq!(ink::env::contains_contract_storage(&key), Some(1));
+            // Delegates deployed before `FlipperRoot` read a lone `bool`.
+            assert_eq!(
+                ink::env::get_contract_storage::<_, bool>(&key),
+                Ok(Some(true))
+            );
+        }
+
+        #[ink::test]
+        fn delegator_state_is_out_of_the_delegates_reach() {
+            use flipper_storage::DELEGATOR_KEYS_START;
+
+            let mut keys = flipper(false).field_keys();
+            assert!(keys.iter().all(|key| *key >= DELEGATOR_KEYS_START));
+            let fields = keys.len();
+            keys.sort_unstable();
+            keys.dedup();
+            assert_eq!(keys.len(), fields);
+        }
+
+        /// Panics fall back to the `unreachable` trap in release builds.
+        /// Clippy's `panic` lint also fires on ink!'s expansion of every
+        /// message, so it can't be denied at the crate level; scan the
+        /// contracts' sources instead, on top of the lints clippy denies.
+        #[test]
+        fn contracts_dont_panic() {
+            let sources = [
+                ("lib.rs", include_str!("lib.rs")),
+                (
+                    "other-contract/lib.rs",
+                    include_str!("other-contract/lib.rs"),
+                ),
+                (
+                    "relay-contract/lib.rs",
+                    include_str!("relay-contract/lib.rs"),
+                ),
+                (
+                    "flipper-storage/lib.rs",
+                    include_str!("flipper-storage/lib.rs"),
+                ),
+            ];
+            let patterns = [
+                ".unwrap()",
+                ".expect(",
+                "panic!(",
+                "unreachable!(",
+                "unimplemented!(",
+                "todo!(",
+            ];
+            for (file, source) in sources {
+                // Everything up to the tests.
+                let code = source.split("#[cfg(test)]").next().unwrap_or_default();
+                for (number, line) in code.lines().enumerate() {
+                    if line.trim_start().starts_with("//") {
+                        continue;
+                    }
+                    for pattern in patterns {
+                        assert!(
+                            !line.contains(pattern),
+                            "{file}:{}: `{pattern}` can panic",
+                            number + 1
+                        );
+                    }
+                }
+            }
+        }
+
+        #[ink::test]
+        fn initialized_instances_cant_be_initialized_again() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.initialize(InitConfig {
+                    owner: Some(accounts.bob),
+                    ..InitConfig::default()
+                }),
+                Err(Error::AlreadyInitialized)
+            );
+            assert_eq!(flipper.owner(), Some(accounts.alice));
+            assert!(!flipper.get());
+        }
+
+        #[ink::test]
+        fn delegation_can_be_configured_later() {
+            let mut flipper = CrossContractFlipper::new_default();
+            assert_eq!(flipper.delegate_to(), None);
+            assert_eq!(flipper.delegate_call_params(), Err(Error::DelegateNotSet));
+            assert_eq!(
+                flipper.flip_with_memo(Vec::new()),
+                Err(Error::DelegateNotSet)
+            );
+            assert_eq!(flipper.chained_flip(Vec::new()), Err(Error::DelegateNotSet));
+            assert_eq!(flipper.call_delegate_flip(), Err(Error::DelegateNotSet));
+            assert_eq!(
+                flipper.set_delegate_to(Hash::from([0x09; 32])),
+                Err(Error::CodeHashNotApproved)
+            );
+        }
+
+        #[ink::test]
+        #[cfg(feature = "governance")]
+        fn admin_keys_rotate_with_a_proof_of_the_old_one() {
+            use ink_e2e::subxt_signer::sr25519::dev;
+
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let (old_key, new_key) = (dev::alice(), dev::bob());
+            let old = AccountId::from(old_key.public_key().0);
+            let new = AccountId::from(new_key.public_key().0);
+            let mut flipper = flipper(false);
+            flipper.owner.set(&old);
+            ink::env::test::set_caller::<Environment>(old);
+            assert_eq!(flipper.add_member(old), Ok(()));
+            assert_eq!(flipper.propose(vec![GovernanceAction::Pause]), Ok(0));
+            assert_eq!(flipper.schedule_flip(5).map(drop), Ok(()));
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(flipper.schedule_flip(6).map(drop), Ok(()));
+
+            let rotation_hash = flipper
+                .key_rotation_hash(new)
+                .expect("the contract has an owner");
+            let proof = old_key.sign(rotation_hash.as_ref()).0;
+            assert_eq!(flipper.rotate_admin(proof, new), Err(Error::NotNewAdmin));
+            ink::env::test::set_caller::<Environment>(new);
+            assert_eq!(
+                flipper.rotate_admin(new_key.sign(b"rotate").0, new),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(flipper.rotate_admin(proof, new), Ok(()));
+            assert_eq!(flipper.owner(), Some(new));
+            assert_eq!(flipper.scheduled_flips().len(), 1);
+            assert_eq!(flipper.vote(0, true), Err(Error::ProposalRevoked));
+            assert_eq!(flipper.execute_proposal(0), Err(Error::ProposalRevoked));
+            // A proof only works once.
+            assert_eq!(
+                flipper.rotate_admin(proof, new),
+                Err(Error::InvalidSignature)
+            );
+
+            assert_eq!(flipper.add_member(new), Ok(()));
+            assert_eq!(flipper.propose(vec![GovernanceAction::Pause]), Ok(1));
+            assert_eq!(flipper.vote(1, true), Ok(()));
+        }
+
+        #[ink::test]
+        fn delegate_changes_need_a_threshold_of_signatures() {
+            use ink_e2e::subxt_signer::sr25519::{dev, Keypair};
+
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            let code_hash = Hash::from([0x09; 32]);
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, Vec::new()),
+                Err(Error::UpgradeSignersNotSet)
+            );
+
+            let (alice, bob, eve) = (dev::alice(), dev::bob(), dev::eve());
+            let account = |keypair: &Keypair| AccountId::from(keypair.public_key().0);
+            let signers = UpgradeSigners {
+                signers: vec![account(&alice), account(&bob)],
+                threshold: 2,
+            };
+            for invalid in [
+                UpgradeSigners {
+                    threshold: 3,
+                    ..signers.clone()
+                },
+                UpgradeSigners {
+                    threshold: 0,
+                    ..signers.clone()
+                },
+                UpgradeSigners {
+                    signers: vec![account(&alice), account(&alice)],
+                    threshold: 1,
+                },
+            ] {
+                assert_eq!(
+                    flipper.set_upgrade_signers(Some(invalid)),
+                    Err(Error::InvalidUpgradeSigners)
+                );
+            }
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                flipper.set_upgrade_signers(Some(signers.clone())),
+                Err(Error::NotOwner)
+            );
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.set_upgrade_signers(Some(signers.clone())), Ok(()));
+            assert_eq!(flipper.upgrade_signers(), Some(signers));
+
+            let action_hash = flipper.upgrade_action_hash(code_hash);
+            let sign = |keypair: &Keypair| UpgradeSignature::Sr25519 {
+                signer: account(keypair),
+                signature: keypair.sign(action_hash.as_ref()).0,
+            };
+            // Anyone can submit the signatures.
+            ink::env::test::set_caller::<Environment>(accounts.charlie);
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, vec![sign(&alice)]),
+                Err(Error::ThresholdNotMet)
+            );
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, vec![sign(&alice), sign(&alice)]),
+                Err(Error::DuplicateSignature)
+            );
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, vec![sign(&alice), sign(&eve)]),
+                Err(Error::NotUpgradeSigner)
+            );
+            assert_eq!(
+                flipper.set_delegate_to_signed(
+                    code_hash,
+                    vec![sign(&alice), UpgradeSignature::Ecdsa([0; 65])]
+                ),
+                Err(Error::InvalidSignature)
+            );
+            let forged = UpgradeSignature::Sr25519 {
+                signer: account(&bob),
+                signature: alice.sign(action_hash.as_ref()).0,
+            };
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, vec![sign(&alice), forged]),
+                Err(Error::InvalidSignature)
+            );
+            // Past the signatures, the code hash is checked like for
+            // `set_delegate_to`.
+            assert_eq!(
+                flipper.set_delegate_to_signed(code_hash, vec![sign(&bob), sign(&alice)]),
+                Err(Error::CodeHashNotApproved)
+            );
+            assert_eq!(flipper.upgrade_nonce(), 0);
+        }
+
+        #[ink::test]
+        fn audit_logs_are_numbered_without_gaps() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_paused(true), Ok(()));
+            flipper.record_call(ink::selector_bytes!("flip"), false);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.set_paused(false), Err(Error::NotOwner));
+            assert_eq!(flipper.audit_seq(), 2);
+
+            let logs: Vec<_> = ink::env::test::recorded_events()
+                .map(|event| AuditLog::decode(&mut &event.data[..]).expect("an AuditLog"))
+                .map(|log| (log.seq, log.kind, log.data))
+                .collect();
+            assert_eq!(
+                logs,
+                vec![
+                    (
+                        0,
+                        AuditKind::Admin,
+                        (accounts.alice, ink::selector_bytes!("set_paused")).encode()
+                    ),
+                    (
+                        1,
+                        AuditKind::CrossCall,
+                        (ink::selector_bytes!("flip"), false).encode()
+                    ),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn only_unreferenced_dependencies_are_unlocked() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.cleanup_dependencies(), Ok(0));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.cleanup_dependencies(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+
+            let [delegate, approved, replaced] =
+                [0x07, 0x08, 0x09].map(|byte| Hash::from([byte; 32]));
+            flipper
+                .locked_code_hashes
+                .set(&vec![delegate, approved, replaced]);
+            flipper.delegate_to.set(&delegate);
+            assert_eq!(flipper.approve_code_hash(approved), Ok(()));
+            assert_eq!(flipper.unreferenced_dependencies(), vec![replaced]);
+
+            flipper.delegation_disabled.set(&true);
+            assert_eq!(
+                flipper.unreferenced_dependencies(),
+                flipper.locked_code_hashes()
+            );
+        }
+
+        #[ink::test]
+        fn delegation_can_be_disabled_for_good() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(true);
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.disable_delegation_forever(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.disable_delegation_forever(), Ok(()));
+            assert!(flipper.delegation_disabled());
+            assert_eq!(flipper.disable_delegation_forever(), Ok(()));
+            // Two `AuditLog`s, but only one `DelegationDisabled`.
+            assert_eq!(ink::env::test::recorded_events().count(), 3);
+
+            let code_hash = Hash::from([0x09; 32]);
+            flipper.delegate_to.set(&code_hash);
+            assert_eq!(
+                flipper.delegate_call_params(),
+                Err(Error::DelegationDisabled)
+            );
+            assert_eq!(flipper.call_delegate_flip(), Err(Error::DelegationDisabled));
+            assert_eq!(
+                flipper.chained_flip(Vec::new()),
+                Err(Error::DelegationDisabled)
+            );
+            assert_eq!(flipper.approve_code_hash(code_hash), Ok(()));
+            assert_eq!(
+                flipper.set_delegate_to(code_hash),
+                Err(Error::DelegationDisabled)
+            );
+            assert_eq!(flipper.set_paused(true), Ok(()));
+            assert!(flipper.get());
+        }
+
+        #[ink::test]
+        fn delegates_with_another_layout_are_refused() {
+            let mut flipper = flipper(true);
+            flipper.delegate_to.set(&Hash::from([0x09; 32]));
+            assert_eq!(flipper.delegate_layout(), None);
+            assert!(flipper.delegate_call_params().is_ok());
+
+            flipper.delegate_layout.set(&Hash::from([0x07; 32]));
+            assert_eq!(flipper.delegate_call_params(), Err(Error::LayoutMismatch));
+            assert_eq!(flipper.call_delegate_flip(), Err(Error::LayoutMismatch));
+
+            let expected = flipper.expected_delegate_layout();
+            flipper.delegate_layout.set(&expected);
+            assert_eq!(flipper.delegate_layout(), Some(expected));
+            assert!(flipper.delegate_call_params().is_ok());
+        }
+
+        #[ink::test]
+        fn instances_are_set_up_from_an_init_config() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let minimum = ink::env::minimum_balance::<Environment>();
+            assert!(matches!(
+                CrossContractFlipper::with_config(InitConfig {
+                    relayer_fee: minimum - 1,
+                    ..InitConfig::default()
+                }),
+                Err(Error::BelowMinimumBalance)
+            ));
+
+            let flipper = CrossContractFlipper::with_config(InitConfig {
+                init_value: true,
+                owner: Some(accounts.bob),
+                relayer_fee: minimum,
+                ..InitConfig::default()
+            })
+            .unwrap();
+            assert!(flipper.get());
+            assert_eq!(flipper.owner(), Some(accounts.bob));
+            assert_eq!(flipper.relayer_fee(), minimum);
+            assert_eq!(flipper.delegate_to(), None);
+        }
+
+        #[ink::test]
+        fn config_is_validated_as_a_whole() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.get_config(), Config::default());
+            let config = Config {
+                cooldown: 3,
+                paused: true,
+                ..Config::default()
+            };
+            assert_eq!(
+                flipper.set_config(Config {
+                    reveal_window: 0,
+                    ..config
+                }),
+                Err(Error::InvalidConfig)
+            );
+            assert_eq!(flipper.set_config(config), Ok(()));
+            assert_eq!(flipper.cooldown(), 3);
+            assert!(flipper.paused());
+            assert_eq!(flipper.set_paused(false), Ok(()));
+            assert_eq!(flipper.get_config().cooldown, 3);
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.set_config(config), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn epochs_reset_the_value_and_counters() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(true);
+            assert_eq!(flipper.set_epoch_config(2, false), Ok(()));
+            flipper.record_flip_by(accounts.bob);
+            flipper.record_flip_by(accounts.bob);
+            assert!(flipper.get());
+            assert_eq!(
+                flipper.epoch_stats(0),
+                EpochStats {
+                    flips: 2,
+                    flippers: 1,
+                }
+            );
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.current_epoch(), 1);
+            assert!(!flipper.get());
+            assert_eq!(flipper.epoch_stats(1), EpochStats::default());
+            assert_eq!(flipper.epoch_flips_of(0, accounts.bob), 2);
+            flipper.settle_value();
+            assert!(!flipper.root.value);
+        }
+
+        #[ink::test]
+        fn scores_decay_over_time() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.set_decay_period(2), Ok(()));
+            flipper.record_flip_by(accounts.bob);
+            flipper.record_flip_by(accounts.bob);
+            flipper.record_flip_by(accounts.bob);
+            assert_eq!(flipper.score_of(accounts.bob), 3);
+
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::advance_block::<Environment>();
+            assert_eq!(flipper.score_of(accounts.bob), 1);
+            assert_eq!(flipper.stats_of(accounts.bob), 3);
+            flipper.record_flip_by(accounts.charlie);
+            flipper.record_flip_by(accounts.charlie);
+            assert_eq!(
+                flipper.leaderboard(),
+                vec![(accounts.charlie, 2), (accounts.bob, 1)]
+            );
+        }
+
+        #[ink::test]
+        fn memos_are_bounded() {
+            let mut flipper = flipper(false);
+            assert_eq!(
+                flipper.flip_with_memo(vec![0; MAX_MEMO_LEN + 1]),
+                Err(Error::MemoTooLong)
+            );
+            let config = Config {
+                max_memo_len: 4,
+                ..Config::default()
+            };
+            assert_eq!(flipper.set_config(config), Ok(()));
+            assert_eq!(flipper.flip_with_memo(vec![0; 5]), Err(Error::MemoTooLong));
+            assert_eq!(
+                flipper.set_config(Config {
+                    max_memo_len: MAX_MEMO_LEN as u32 + 1,
+                    ..config
+                }),
+                Err(Error::InvalidConfig)
+            );
+        }
+
+        #[ink::test]
+        fn invariant_violations_are_reported() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            flipper.count_flip();
+            flipper.record_flip_by(accounts.bob);
+            flipper.count_flip();
+            flipper.record_flip_by(accounts.charlie);
+            assert_eq!(flipper.check_invariants(), Ok(Vec::new()));
+
+            flipper.history_len.set(&3);
+            flipper
+                .leaderboard
+                .set(&vec![(accounts.bob, 1), (accounts.charlie, 2)]);
+            flipper.entered.set(&true);
+            assert_eq!(
+                flipper.check_invariants(),
+                Ok(vec![
+                    Violation::HistoryAheadOfFlips {
+                        history_len: 3,
+                        attributed_flips: 2,
+                    },
+                    Violation::LeaderboardUnsorted,
+                    Violation::LeaderboardAheadOfFlips {
+                        sum: 3,
+                        attributed_flips: 2,
+                    },
+                    Violation::LockHeld,
+                ])
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.check_invariants(), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn a_stuck_lock_can_be_cleared() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let mut flipper = flipper(false);
+            let flip = String::from("flip");
+            let selector = ink::selector_bytes!("flip");
+            assert_eq!(flipper.register_selector(flip.clone(), selector), Ok(()));
+            assert!(!flipper.is_entered());
+
+            flipper.entered.set(&true);
+            assert!(flipper.is_entered());
+            assert_eq!(
+                flipper.call_named(AccountId::from([0x09; 32]), flip, Vec::new(), None, None),
+                Err(Error::Reentered)
+            );
+
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(flipper.force_clear_lock(), Err(Error::NotOwner));
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(flipper.force_clear_lock(), Ok(()));
+            assert!(!flipper.is_entered());
+        }
+
+        #[ink::test]
+        fn callers_can_override_the_call_limits() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.call_limits(None), WeightLimits::default());
+            let configured = WeightLimits {
+                ref_time: 1_000_000_000,
+                proof_size: 64 * 1024,
+            };
+            assert_eq!(
+                flipper.set_config(Config {
+                    call_limits: Some(configured),
+                    ..Config::default()
+                }),
+                Ok(())
+            );
+            assert_eq!(flipper.call_limits(None), configured);
+            let custom = WeightLimits {
+                ref_time: 2_000_000_000,
+                proof_size: 0,
+            };
+            assert_eq!(flipper.call_limits(Some(custom)), custom);
+        }
+
+        #[ink::test]
+        fn forwarded_inputs_are_bounded() {
+            let mut flipper = flipper(false);
+            let flip = String::from("flip");
+            let selector = ink::selector_bytes!("flip");
+            assert_eq!(flipper.register_selector(flip.clone(), selector), Ok(()));
+            let target = AccountId::from([0x09; 32]);
+            let input = vec![0; DEFAULT_MAX_INPUT_LEN as usize + 1];
+            assert_eq!(
+                flipper.call_named(target, flip.clone(), input.clone(), None, None),
+                Err(Error::InputTooLarge { index: 0 })
+            );
+            let calls = vec![
+                EncodedCall {
+                    target,
+                    selector,
+                    input: Vec::new(),
+                    expected_code_hash: None,
+                    limits: None,
+                };
+                2
+            ];
+            let mut oversized = calls.clone();
+            oversized[1].input = vec![0; 9];
+            let config = Config {
+                max_input_len: 8,
+                ..Config::default()
+            };
+            assert_eq!(flipper.set_config(config), Ok(()));
+            assert_eq!(
+                flipper.multicall(oversized[1..].to_vec()),
+                Err(Error::InputTooLarge { index: 0 })
+            );
+            let results = flipper.multicall_best_effort(oversized);
+            assert_eq!(
+                results.map(|mut results| results.pop()),
+                Ok(Some(Err(Error::InputTooLarge { index: 1 })))
+            );
+            assert_eq!(
+                flipper.set_config(Config {
+                    max_input_len: MAX_INPUT_LEN + 1,
+                    ..config
+                }),
+                Err(Error::InvalidConfig)
+            );
+        }
+
+        #[ink::test]
+        fn queries_need_the_other_contract() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.query_other_value(), Err(Error::OtherContractNotSet));
+
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            assert_eq!(
+                flipper.set_other_contract(accounts.django),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(flipper.other_contract(), None);
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                flipper.set_other_contract(accounts.django),
+                Err(Error::NotAContract)
+            );
+        }
+
+        #[ink::test]
+        fn delegate_gas_average_follows_new_samples() {
+            let mut flipper = flipper(false);
+            assert_eq!(flipper.avg_delegate_gas(), 0);
+
+            flipper.record_delegate_gas(800);
+            assert_eq!(flipper.avg_delegate_gas(), 800);
+            flipper.record_delegate_gas(1_600);
+            assert_eq!(flipper.avg_delegate_gas(), 900);
+        }
+
+        #[ink::test]
+        fn payouts_have_to_endow_new_accounts() {
+            let mut flipper = flipper(false);
+            let minimum = ink::env::minimum_balance::<Environment>();
+            assert_eq!(
+                flipper.set_relayer_fee(minimum - 1),
+                Err(Error::BelowMinimumBalance)
+            );
+            assert_eq!(flipper.set_keeper_bounty(0), Ok(()));
+            assert_eq!(flipper.set_keeper_bounty(minimum), Ok(()));
+        }
+
+        #[ink::test]
+        fn transfers_out_leave_the_fee_pool() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let contract = ink::env::account_id::<Environment>();
+            let minimum = ink::env::minimum_balance::<Environment>();
+            let mut flipper = flipper(false);
+            ink::env::test::set_account_balance::<Environment>(contract, 3 * minimum);
+            flipper.fee_pool.set(&minimum);
+
+            assert_eq!(
+                flipper.transfer_out(accounts.bob, 3 * minimum),
+                Err(Error::InsufficientBalance)
+            );
+            let before = ink::env::test::get_account_balance::<Environment>(accounts.bob);
+            assert_eq!(flipper.transfer_out(accounts.bob, 2 * minimum), Ok(()));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(accounts.bob),
+                before.map(|balance| balance + 2 * minimum)
+            );
+        }
+
+        #[ink::test]
+        #[cfg(feature = "auctions")]
+        fn transfers_out_leave_escrowed_bids() {
+            let accounts = ink::env::test::default_accounts::<Environment>();
+            let contract = ink::env::account_id::<Environment>();
+            let minimum = ink::env::minimum_balance::<Environment>();
+            let mut flipper = flipper(false);
+            ink::env::test::set_account_balance::<Environment>(contract, 3 * minimum);
+            assert_eq!(flipper.start_auction(1), Ok(0));
+            ink::env::test::set_caller::<Environment>(accounts.bob);
+            ink::env::test::set_value_transferred::<Environment>(2 * minimum);
+            assert_eq!(flipper.bid(), Ok(()));
+            assert_eq!(flipper.validate_migration().owed, 2 * minimum);
+
+            ink::env::test::set_caller::<Environment>(accounts.alice);
+            assert_eq!(
+                flipper.transfer_out(accounts.charlie, 2 * minimum),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(flipper.transfer_out(accounts.charlie, minimum), Ok(()));
+            assert_eq!(flipper.validate_migration().owed, 2 * minimum);
+        }
+
+        #[ink::test]
+        fn amounts_and_ids_dont_overflow() {
+            let mut flipper = flipper(false);
+            flipper.fee_pool.set(&Balance::MAX);
+            ink::env::test::set_value_transferred::<Environment>(1);
+            assert_eq!(flipper.fund_fee_pool(), Err(Error::Overflow));
+            assert_eq!(flipper.fee_pool(), Balance::MAX);
+
+            flipper.next_schedule_id.set(&u64::MAX);
+            ink::env::test::set_value_transferred::<Environment>(ink::env::minimum_balance::<
+                Environment,
+            >());
+            assert_eq!(flipper.schedule_flip(5), Err(Error::Overflow));
+            assert!(flipper.scheduled_flips().is_empty());
+        }
+
+        #[test]
+        fn error_codes_are_stable() {
+            assert_eq!(Error::NotOwner.code(), 1);
+            assert_eq!(Error::CooldownActive { until: 7 }.code(), 25);
+            assert_eq!(Error::MulticallFailed { index: 2 }.code(), 53);
+            assert_eq!(Error::Overflow.code(), 86);
+            assert_eq!(Error::ReturnDataTooLarge { index: 0 }.code(), 89);
+            assert_eq!(Error::InputTooLarge { index: 0 }.code(), 90);
+            assert_eq!(Error::Reentered.code(), 91);
+            assert_eq!(Error::DelegationDisabled.code(), 92);
+            assert_eq!(Error::ThresholdNotMet.code(), 97);
+            assert_eq!(Error::MessageDisabled.code(), 100);
+            assert_eq!(Error::ClaimPeriodOpen.code(), 101);
+            assert_eq!(Error::NothingToSettle.code(), 102);
+            assert_eq!(Error::TooManyScheduledFlips.code(), 103);
+        }
+
+        #[test]
+        fn oversized_return_data_is_rejected() {
+            let output = vec![0x07; MAX_RETURN_DATA_LEN];
+            assert_eq!(
+                RawOutput::decode(&mut &output[..]),
+                Ok(RawOutput(output.clone()))
+            );
+            let output = vec![0x07; MAX_RETURN_DATA_LEN + 1];
+            assert!(RawOutput::decode(&mut &output[..]).is_err());
+        }
+
+        #[ink::test]
+        fn selectors_are_registered_by_name() {
+            let mut flipper = flipper(false);
+            let flip = String::from("flip");
+            assert_eq!(
+                flipper.call_named(
+                    AccountId::from([0x09; 32]),
+                    flip.clone(),
+                    Vec::new(),
+                    None,
+                    None
+                ),
+                Err(Error::SelectorNotRegistered)
+            );
+            let selector = ink::selector_bytes!("flip");
+            assert_eq!(flipper.register_selector(flip.clone(), selector), Ok(()));
+            assert_eq!(flipper.register_selector(flip.clone(), selector), Ok(()));
+            assert_eq!(flipper.registered_selector(flip.clone()), Some(selector));
+            assert_eq!(flipper.selector_of(flip.clone()), selector);
+            assert_eq!(flipper.selector_names(), vec![flip.clone()]);
+            assert_eq!(flipper.unregister_selector(flip.clone()), Ok(()));
+            assert_eq!(flipper.registered_selector(flip), None);
+            assert!(flipper.selector_names().is_empty());
         }
 
-        fn delegate_to(&self) -> Hash {
-            self.delegate_to
-                .get()
-                .expect("Delegate to always has a value")
+        #[ink::test]
+        #[cfg(feature = "meta-tx")]
+        fn signed_flips_reject_invalid_signatures() {
+            let mut flipper = flipper(false);
+            let payload = FlipPayload {
+                contract: ink::env::account_id::<Environment>(),
+                nonce: 0,
+                idempotency_key: None,
+            };
+            let signer = AccountId::from([0x02; 32]);
+
+            assert_eq!(
+                flipper.flip_with_signature(payload.clone(), [0; 65]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                flipper.flip_with_sr25519(payload, signer, [0; 64]),
+                Err(Error::InvalidSignature)
+            );
+            assert_eq!(
+                flipper.flip_with_sr25519(
+                    FlipPayload {
+                        contract: signer,
+                        nonce: 0,
+                        idempotency_key: None,
+                    },
+                    signer,
+                    [0; 64]
+                ),
+                Err(Error::WrongContract)
+            );
         }
 
-        /// Returns the current value in storage
-        #[ink(message)]
-        pub fn get(&self) -> bool {
-            self.value
+        #[ink::test]
+        #[cfg(feature = "meta-tx")]
+        fn retried_signed_flips_run_once() {
+            use ink_e2e::subxt_signer::sr25519::dev;
+
+            let mut flipper = flipper(false);
+            let keypair = dev::alice();
+            let signer = AccountId::from(keypair.public_key().0);
+            let key = [0x07; 32];
+            let payload = FlipPayload {
+                contract: ink::env::account_id::<Environment>(),
+                nonce: 0,
+                idempotency_key: Some(key),
+            };
+            let signature = keypair.sign(&payload.encode()).0;
+
+            // As if the first submission went through.
+            flipper.remember_idempotency_key(signer, key);
+            assert_eq!(
+                flipper.flip_with_sr25519(payload.clone(), signer, signature),
+                Ok(())
+            );
+            assert_eq!(flipper.nonce_of(signer), 0);
+
+            let other = AccountId::from([0x02; 32]);
+            for index in 0..IDEMPOTENCY_KEYS as u8 {
+                flipper.remember_idempotency_key(other, [index; 32]);
+            }
+            assert!(!flipper.is_idempotency_key_used(signer, key));
+            assert!(flipper.is_idempotency_key_used(other, [1; 32]));
+            assert_eq!(
+                flipper.flip_with_sr25519(payload, signer, signature),
+                Err(Error::DelegateNotSet)
+            );
         }
     }
 
     #[cfg(all(test, feature = "e2e-tests"))]
     mod e2e_tests {
         use super::*;
-        use ink_e2e::{ChainBackend, ContractsBackend};
+        use ink_e2e::{ChainBackend, ContractsBackend, PolkadotConfig, RpcClient, TestNodeProcess};
+        use std::future::Future;
 
         type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+        type Client = ink_e2e::Client<PolkadotConfig, crate::FlipperEnvironment>;
+        /// Client for `other-contract`, which runs with the default environment.
+        type OtherClient = ink_e2e::Client<PolkadotConfig, ink::env::DefaultEnvironment>;
+
+        /// Points the tests at an already running node, e.g. one provided by
+        /// CI, instead of spawning a fresh `substrate-contracts-node`.
+        ///
+        /// Unlike with `#[ink_e2e::test]`, it is read when the tests run, so
+        /// changing it doesn't require a rebuild.
+        const NODE_URL_VAR: &str = "CONTRACTS_NODE_URL";
+
+        /// Builds the contracts and runs `test` against the node at
+        /// `NODE_URL_VAR`, or against a freshly spawned node if it isn't set.
+        ///
+        /// The spawned node's binary can be chosen with `CONTRACTS_NODE`.
+        fn run_e2e<F, Fut>(test: F) -> E2EResult<()>
+        where
+            F: FnOnce(Client, OtherClient) -> Fut,
+            Fut: Future<Output = E2EResult<()>>,
+        {
+            ink_e2e::tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(async {
+                    let contracts = ink_e2e::build_root_and_contract_dependencies();
+                    // Keeps a spawned node alive until the test is done.
+                    let (rpc, _node) = match std::env::var(NODE_URL_VAR) {
+                        Ok(url) => (RpcClient::from_url(&url).await?, None),
+                        Err(_) => {
+                            let node =
+                                TestNodeProcess::<PolkadotConfig>::build_with_env_or_default()
+                                    .spawn()
+                                    .await?;
+                            (node.rpc(), Some(node))
+                        }
+                    };
+                    let client = Client::new(rpc.clone(), contracts.clone()).await?;
+                    let other_client = OtherClient::new(rpc, contracts).await?;
+                    test(client, other_client).await
+                })
+        }
+
+        #[test]
+        fn e2e_flip_test() -> E2EResult<()> {
+            run_e2e(|client, _| flip_test(client))
+        }
 
-        #[ink_e2e::test]
-        async fn e2e_flip_test<Client: E2EBackend>(mut client: Client) -> E2EResult<()> {
+        async fn flip_test(mut client: Client) -> E2EResult<()> {
             let origin = client
                 .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
                 .await;
@@ -105,10 +7759,19 @@ mod cross_contract_flipper {
 
             let call_delegate_flip = call_builder.call_delegate_flip();
 
-            let result = client.call(&origin, &call_delegate_flip).submit().await;
-            assert!(result.is_ok(), "Calling `delegate_flip` failed");
+            let outcome = client
+                .call(&origin, &call_delegate_flip)
+                .submit()
+                .await
+                .expect("Calling `call_delegate_flip` failed")
+                .return_value()
+                .expect("`call_delegate_flip` returned an error");
+            assert_eq!(outcome.target, code_hash);
+            assert_eq!(outcome.flags, 0);
+            assert!(outcome.value);
+            assert!(outcome.gas_used > 0, "Expected the delegate to use gas");
 
-            let expected_value = false;
+            let expected_value = true;
             let call_builder = contract.call_builder::<CrossContractFlipper>();
 
             let call_get = call_builder.get();
@@ -120,10 +7783,7 @@ mod cross_contract_flipper {
                 .unwrap()
                 .return_value();
 
-            assert_eq!(
-                call_get_result, expected_value,
-                "Expected value to be false"
-            );
+            assert_eq!(call_get_result, expected_value, "Expected value to be true");
 
             Ok(())
 
@@ -147,5 +7807,525 @@ mod cross_contract_flipper {
 
             // Ok(())
         }
+
+        #[test]
+        fn e2e_delegate_modes_benchmark() -> E2EResult<()> {
+            run_e2e(|client, _| delegate_modes_benchmark(client))
+        }
+
+        /// Dry-runs the first flip of a fresh instance in both
+        /// `DelegateMode`s, checks that the tail call costs less and prints
+        /// what each costs; run with `--nocapture` to see the figures.
+        async fn delegate_modes_benchmark(mut client: Client) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            let decoded = client
+                .call(
+                    &origin,
+                    &call_builder.delegate_flip_with(DelegateMode::Decoded),
+                )
+                .dry_run()
+                .await?;
+            let tail_call = client
+                .call(
+                    &origin,
+                    &call_builder.delegate_flip_with(DelegateMode::TailCall),
+                )
+                .dry_run()
+                .await?;
+            assert!(!decoded.is_err(), "Expected the decoded flip to succeed");
+            assert!(!tail_call.is_err(), "Expected the tail call to succeed");
+
+            for (mode, result) in [("Decoded", &decoded), ("TailCall", &tail_call)] {
+                println!(
+                    "{mode}: weight {:?}, storage deposit {:?}",
+                    result.exec_result.gas_consumed, result.exec_result.storage_deposit
+                );
+            }
+            println!(
+                "Difference: ref_time {}, storage deposit {}",
+                decoded
+                    .exec_result
+                    .gas_consumed
+                    .ref_time()
+                    .abs_diff(tail_call.exec_result.gas_consumed.ref_time()),
+                decoded
+                    .exec_result
+                    .storage_deposit
+                    .charge_or_zero()
+                    .abs_diff(tail_call.exec_result.storage_deposit.charge_or_zero())
+            );
+            let (decoded_run, tail_call_run) = (&decoded.exec_result, &tail_call.exec_result);
+            assert!(
+                tail_call_run.gas_consumed.ref_time() < decoded_run.gas_consumed.ref_time(),
+                "Expected the tail call to use less ref_time"
+            );
+            assert!(
+                tail_call_run.storage_deposit.charge_or_zero()
+                    <= decoded_run.storage_deposit.charge_or_zero(),
+                "Expected the tail call not to need more storage deposit"
+            );
+            // The tail call's output is the delegate's, which doesn't decode
+            // as the message's return type.
+            assert!(matches!(decoded.return_value(), Ok(Some(_))));
+
+            Ok(())
+        }
+
+        #[test]
+        fn e2e_call_other_set_test() -> E2EResult<()> {
+            run_e2e(call_other_set_test)
+        }
+
+        async fn call_other_set_test(
+            mut client: Client,
+            mut other_client: OtherClient,
+        ) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let mut other_constructor = other_contract::OtherContractRef::new(false);
+            let other = other_client
+                .instantiate("other-contract", &origin, &mut other_constructor)
+                .submit()
+                .await
+                .expect("other-contract instantiate failed");
+            // The code is stored already, so this only looks up its hash.
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            client
+                .call(&origin, &call_builder.set_other_contract(other.account_id))
+                .submit()
+                .await
+                .expect("Calling `set_other_contract` failed");
+            client
+                .call(&origin, &call_builder.call_other_set(true, None))
+                .submit()
+                .await
+                .expect("Calling `call_other_set` failed");
+
+            let value = client
+                .call(&origin, &call_builder.query_other_value())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(value, Ok(true), "Expected the other contract to be set");
+
+            Ok(())
+        }
+
+        #[test]
+        fn e2e_migrate_from_test() -> E2EResult<()> {
+            run_e2e(|client, _| migrate_from_test(client))
+        }
+
+        async fn migrate_from_test(mut client: Client) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+
+            let mut old_constructor = CrossContractFlipperRef::new(true, code_hash);
+            let old = client
+                .instantiate("cross-contract-flipper", &origin, &mut old_constructor)
+                .submit()
+                .await
+                .expect("old cross-contract-flipper instantiate failed");
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            client
+                .call(&origin, &call_builder.migrate_from(old.account_id))
+                .submit()
+                .await
+                .expect("Calling `migrate_from` failed");
+
+            let value = client
+                .call(&origin, &call_builder.get())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(value, "Expected the old contract's value");
+            let again = client
+                .call(&origin, &call_builder.migrate_from(old.account_id))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(again, Err(Error::AlreadyMigrated));
+
+            Ok(())
+        }
+
+        #[test]
+        fn e2e_chained_flip_test() -> E2EResult<()> {
+            run_e2e(|client, _| chained_flip_test(client))
+        }
+
+        async fn chained_flip_test(mut client: Client) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+            let relay = client
+                .upload("relay-contract", &origin)
+                .submit()
+                .await
+                .expect("relay_contract upload failed")
+                .code_hash;
+
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            client
+                .call(&origin, &call_builder.approve_code_hash(relay))
+                .submit()
+                .await
+                .expect("Calling `approve_code_hash` failed");
+            // A -> relay -> relay -> other-contract, all on A's storage.
+            client
+                .call(&origin, &call_builder.chained_flip(vec![relay, relay]))
+                .submit()
+                .await
+                .expect("Calling `chained_flip` failed");
+
+            let value = client
+                .call(&origin, &call_builder.get())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(value, "Expected the chain to flip the value");
+            let depth = client
+                .call(&origin, &call_builder.delegate_depth())
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(depth, 0, "Expected the relays to unwind the depth");
+
+            let too_deep = vec![relay; relay_contract::MAX_DEPTH as usize + 1];
+            let result = client
+                .call(&origin, &call_builder.chained_flip(too_deep))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(result, Err(Error::DelegateDepthExceeded));
+
+            Ok(())
+        }
+
+        #[test]
+        fn e2e_multicall_revert_test() -> E2EResult<()> {
+            run_e2e(multicall_revert_test)
+        }
+
+        /// Batches a call that reverts, since `relay` returns `Err` without
+        /// hops, with one that succeeds.
+        async fn multicall_revert_test(
+            mut client: Client,
+            mut other_client: OtherClient,
+        ) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let mut other_constructor = other_contract::OtherContractRef::new(false);
+            let other = other_client
+                .instantiate("other-contract", &origin, &mut other_constructor)
+                .submit()
+                .await
+                .expect("other-contract instantiate failed");
+            let mut relay_constructor = relay_contract::RelayContractRef::new();
+            let relay = other_client
+                .instantiate("relay-contract", &origin, &mut relay_constructor)
+                .submit()
+                .await
+                .expect("relay-contract instantiate failed");
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            let calls = vec![
+                EncodedCall {
+                    target: relay.account_id,
+                    selector: ink::selector_bytes!("relay"),
+                    input: Vec::<Hash>::new().encode(),
+                    expected_code_hash: None,
+                    limits: None,
+                },
+                EncodedCall {
+                    target: other.account_id,
+                    selector: ink::selector_bytes!("get"),
+                    input: Vec::new(),
+                    expected_code_hash: None,
+                    limits: None,
+                },
+            ];
+            let result = client
+                .call(&origin, &call_builder.multicall_best_effort(calls.clone()))
+                .submit()
+                .await
+                .expect("Calling `multicall_best_effort` failed");
+            let failures: Vec<_> = result
+                .contract_emitted_events()?
+                .into_iter()
+                .filter(|event| {
+                    event.topics.first().map(|topic| topic.0)
+                        == <CallFailed as ink::env::Event>::SIGNATURE_TOPIC
+                })
+                .map(|event| CallFailed::decode(&mut &event.event.data[..]).expect("a CallFailed"))
+                .map(|failed| (failed.index, failed.code))
+                .collect();
+            assert_eq!(failures, [(0, Error::MulticallFailed { index: 0 }.code())]);
+            assert_eq!(
+                result.return_value(),
+                Ok(vec![
+                    Err(Error::MulticallFailed { index: 0 }),
+                    Ok(false.encode()),
+                ])
+            );
+
+            let result = client
+                .call(&origin, &call_builder.multicall(calls))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(result, Err(Error::MulticallFailed { index: 0 }));
+
+            Ok(())
+        }
+
+        #[test]
+        #[cfg(feature = "governance")]
+        fn e2e_governance_test() -> E2EResult<()> {
+            run_e2e(|client, _| governance_test(client))
+        }
+
+        /// Walks proposals through the members' votes: one that passes and
+        /// takes effect, one voted down and one nobody voted on in time.
+        #[cfg(feature = "governance")]
+        async fn governance_test(mut client: Client) -> E2EResult<()> {
+            let origin = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+            let bob = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+            let charlie = client
+                .create_and_fund_account(&ink_e2e::alice(), 10_000_000_000_000)
+                .await;
+
+            let code_hash = client
+                .upload("other-contract", &origin)
+                .submit()
+                .await
+                .expect("other_contract upload failed")
+                .code_hash;
+
+            let mut constructor = CrossContractFlipperRef::new(false, code_hash);
+            let contract = client
+                .instantiate("cross-contract-flipper", &origin, &mut constructor)
+                .submit()
+                .await
+                .expect("cross-contract-flipper instantiate failed");
+            let mut call_builder = contract.call_builder::<CrossContractFlipper>();
+
+            for member in [&bob, &charlie] {
+                let account = AccountId::from(member.public_key().0);
+                client
+                    .call(&origin, &call_builder.add_member(account))
+                    .submit()
+                    .await
+                    .expect("Calling `add_member` failed");
+            }
+            let config = GovernanceConfig {
+                quorum_percent: 50,
+                voting_period: 8,
+                execution_delay: 0,
+            };
+            client
+                .call(&origin, &call_builder.set_governance_config(config))
+                .submit()
+                .await
+                .expect("Calling `set_governance_config` failed");
+
+            // Bob and Charlie both approve pausing.
+            let passed = client
+                .call(&bob, &call_builder.propose(vec![GovernanceAction::Pause]))
+                .submit()
+                .await
+                .expect("Calling `propose` failed")
+                .return_value()
+                .expect("`propose` returned an error");
+            for member in [&bob, &charlie] {
+                client
+                    .call(member, &call_builder.vote(passed, true))
+                    .submit()
+                    .await
+                    .expect("Calling `vote` failed");
+            }
+            let early = client
+                .call(&charlie, &call_builder.execute_proposal(passed))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(early, Err(Error::VotingOpen));
+
+            // Bob is for resuming, Charlie against, so it doesn't pass.
+            let rejected = client
+                .call(&bob, &call_builder.propose(vec![GovernanceAction::Unpause]))
+                .submit()
+                .await
+                .expect("Calling `propose` failed")
+                .return_value()
+                .expect("`propose` returned an error");
+            for (member, support) in [(&bob, true), (&charlie, false)] {
+                client
+                    .call(member, &call_builder.vote(rejected, support))
+                    .submit()
+                    .await
+                    .expect("Calling `vote` failed");
+            }
+
+            // Nobody votes on this one before its voting period ends.
+            let expired = client
+                .call(
+                    &charlie,
+                    &call_builder.propose(vec![GovernanceAction::SetRelayerFee(0)]),
+                )
+                .submit()
+                .await
+                .expect("Calling `propose` failed")
+                .return_value()
+                .expect("`propose` returned an error");
+
+            // Every transaction is a block of its own on a dev node.
+            let ends_at = client
+                .call(&origin, &call_builder.proposal(expired))
+                .dry_run()
+                .await?
+                .return_value()
+                .expect("Expected the proposal to exist")
+                .ends_at;
+            for _ in 0..ends_at {
+                let closed = client
+                    .call(&bob, &call_builder.vote(expired, true))
+                    .dry_run()
+                    .await?
+                    .return_value();
+                if closed == Err(Error::VotingClosed) {
+                    break;
+                }
+                client
+                    .call(&origin, &call_builder.get())
+                    .submit()
+                    .await
+                    .expect("Calling `get` failed");
+            }
+
+            let executed = client
+                .call(&charlie, &call_builder.execute_proposal(passed))
+                .submit()
+                .await
+                .expect("Calling `execute_proposal` failed")
+                .return_value();
+            assert_eq!(executed, Ok(()));
+            let paused = client
+                .call(&origin, &call_builder.paused())
+                .dry_run()
+                .await?
+                .return_value();
+            assert!(paused, "Expected the passed proposal to pause flipping");
+            let again = client
+                .call(&bob, &call_builder.execute_proposal(passed))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(again, Err(Error::AlreadyExecuted));
+
+            let result = client
+                .call(&bob, &call_builder.execute_proposal(rejected))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(result, Err(Error::ProposalRejected));
+
+            let late_vote = client
+                .call(&bob, &call_builder.vote(expired, true))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(late_vote, Err(Error::VotingClosed));
+            let result = client
+                .call(&charlie, &call_builder.execute_proposal(expired))
+                .dry_run()
+                .await?
+                .return_value();
+            assert_eq!(result, Err(Error::ProposalRejected));
+
+            Ok(())
+        }
     }
 }
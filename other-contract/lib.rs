@@ -1,4 +1,15 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
 
 pub use self::other_contract::{
     OtherContractRef,
@@ -6,31 +17,45 @@ pub use self::other_contract::{
 
 #[ink::contract]
 mod other_contract {
+    use flipper_storage::FlipperRoot;
 
+    /// The root cell the delegator keeps for its logic contracts.
     #[ink(storage)]
     pub struct OtherContract {
-        value: bool,
+        root: FlipperRoot,
     }
 
     impl OtherContract {
         #[ink(constructor)]
         pub fn new(init_value: bool) -> Self {
-            Self { value: init_value }
+            Self {
+                root: FlipperRoot { value: init_value },
+            }
         }
 
         #[ink(message)]
         pub fn flip(&mut self) {
-            self.value = !self.value;
+            self.root.value = !self.root.value;
         }
 
         #[ink(message)]
         pub fn get(&self) -> bool {
-            self.value
+            self.root.value
         }
 
         #[ink(message)]
         pub fn set(&mut self, value: bool) {
-            self.value = value;
+            self.root.value = value;
+        }
+
+        /// Returns a fingerprint of this contract's storage layout, a
+        /// `FlipperRoot` in the root cell, which a delegator checks against
+        /// its own before it delegates here.
+        #[ink(message)]
+        pub fn layout_hash(&self) -> Hash {
+            Hash::from(FlipperRoot::layout_hash(
+                <Self as ink::storage::traits::StorageKey>::KEY,
+            ))
         }
 
         // Return account_id of the contract
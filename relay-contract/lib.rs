@@ -0,0 +1,105 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
+
+pub use self::relay_contract::{Error, RelayContractRef, MAX_DEPTH};
+pub use flipper_storage::DEPTH_KEY;
+
+/// A logic contract that passes a delegate call on to the next logic
+/// contract of a chain.
+///
+/// It only ever runs through delegate calls, so it works on the storage of
+/// the contract at the top of the chain, as do all contracts below it.
+#[ink::contract]
+mod relay_contract {
+    use flipper_storage::{FlipperRoot, DEPTH_KEY};
+    use ink::{
+        env::call::{build_call, ExecutionInput, Selector},
+        prelude::vec::Vec,
+    };
+
+    /// Maximum number of relays in a chain.
+    pub const MAX_DEPTH: u32 = 4;
+
+    #[derive(Debug, PartialEq, Eq)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum Error {
+        /// The chain has more than `MAX_DEPTH` relays.
+        DepthExceeded,
+        /// There is no contract to pass the call on to.
+        EmptyChain,
+        /// The delegate call into the next contract failed.
+        DelegateCallFailed,
+    }
+
+    /// Matches the root cell of the contract at the top of the chain.
+    #[ink(storage)]
+    pub struct RelayContract {
+        root: FlipperRoot,
+    }
+
+    impl RelayContract {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                root: FlipperRoot::default(),
+            }
+        }
+
+        /// Delegates to the first of `hops`, calling `relay` with the other
+        /// hops if there are any and `flip` if it is the last one.
+        ///
+        /// Takes `&self`, so ink! doesn't write the root cell back over the
+        /// changes the contracts further down the chain made to it.
+        #[ink(message)]
+        pub fn relay(&self, hops: Vec<Hash>) -> Result<(), Error> {
+            let depth = ink::env::get_contract_storage::<_, u32>(&DEPTH_KEY)
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            if depth >= MAX_DEPTH {
+                return Err(Error::DepthExceeded);
+            }
+            let (next, rest) = hops.split_first().ok_or(Error::EmptyChain)?;
+
+            ink::env::set_contract_storage(&DEPTH_KEY, &(depth + 1));
+            let result = if rest.is_empty() {
+                build_call::<Environment>()
+                    .delegate(*next)
+                    .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                        "flip"
+                    ))))
+                    .returns::<()>()
+                    .try_invoke()
+                    .map_err(|_| Error::DelegateCallFailed)?
+                    .map_err(|_| Error::DelegateCallFailed)
+            } else {
+                build_call::<Environment>()
+                    .delegate(*next)
+                    .exec_input(
+                        ExecutionInput::new(Selector::new(ink::selector_bytes!("relay")))
+                            .push_arg(rest.to_vec()),
+                    )
+                    .returns::<Result<(), Error>>()
+                    .try_invoke()
+                    .map_err(|_| Error::DelegateCallFailed)?
+                    .map_err(|_| Error::DelegateCallFailed)?
+            };
+            if depth == 0 {
+                ink::env::clear_contract_storage(&DEPTH_KEY);
+            } else {
+                ink::env::set_contract_storage(&DEPTH_KEY, &depth);
+            }
+            result
+        }
+    }
+}
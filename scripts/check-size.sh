@@ -0,0 +1,20 @@
+#!/usr/bin/env bash
+# Builds the minimal and the full contract and compares the size of their
+# Wasm blobs. Fails if leaving the optional subsystems out doesn't shrink it.
+set -euo pipefail
+cd "$(dirname "$0")/.."
+
+wasm=target/ink/cross_contract_flipper.wasm
+
+# The full build goes last, so it's the one left in `target/ink`.
+cargo contract build --release --quiet --features minimal
+minimal=$(wc -c < "$wasm")
+cargo contract build --release --quiet --features full
+full=$(wc -c < "$wasm")
+
+echo "full:    $full bytes"
+echo "minimal: $minimal bytes ($((100 * minimal / full))% of full)"
+if [ "$minimal" -ge "$full" ]; then
+    echo "the minimal build isn't smaller than the full one" >&2
+    exit 1
+fi